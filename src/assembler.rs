@@ -0,0 +1,885 @@
+//! Assembler for LC-3 source text.
+//! The source is read in a single pass; label operands are recorded as fixups and
+//! patched once every label is known, so forward references work.
+//!
+//! Supports `.ORIG`, `.FILL`, `.BLKW`, `.STRINGZ`, `.END`, labels, every opcode
+//! mnemonic and the trap aliases. As an extension to standard LC-3 syntax,
+//! `.BLKW n, value` fills the `n` reserved words with `value` instead of zero.
+//! `assemble_program` also keeps the symbol table and source lines for tooling
+//! such as the `unreachable_code` lint.
+//! `assemble_with_defines` adds `.IF name`/`.ELSE`/`.ENDIF` blocks gated on
+//! whether `name` is defined.
+
+use std::collections::{BTreeMap, HashMap};
+
+use crate::disassembler::disassemble;
+use crate::types::{LC3Error, Opcodes, TrapVectors, extract_opcode};
+
+/// A label operand waiting for the label's address
+struct Fixup {
+    /// Index of the word to patch
+    index: usize,
+    label: String,
+    /// Width of the PC-relative field, or 16 for an absolute `.FILL` address
+    bits: u32,
+    line: usize,
+}
+
+#[derive(Default)]
+struct Assembler {
+    origin: Option<u16>,
+    words: Vec<u16>,
+    /// Source line of each word
+    lines: Vec<usize>,
+    /// Whether each word is an instruction rather than data
+    code: Vec<bool>,
+    labels: HashMap<String, u16>,
+    fixups: Vec<Fixup>,
+    line: usize,
+    /// Symbols `.IF` tests against
+    defines: Vec<String>,
+    /// Open `.IF` blocks, innermost last
+    conditions: Vec<Condition>,
+}
+
+/// One open `.IF` block
+struct Condition {
+    /// Whether the current branch is assembled (ignoring enclosing blocks)
+    active: bool,
+    seen_else: bool,
+}
+
+/// An assembled program together with what the assembler learned about its source
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AssembledProgram {
+    pub origin: u16,
+    pub words: Vec<u16>,
+    /// Label name to address
+    pub symbols: BTreeMap<String, u16>,
+    /// Source line (1-based) each word was assembled from
+    pub lines: Vec<usize>,
+    code: Vec<bool>,
+}
+
+/// Assemble `source`, returning the `.ORIG` address and the program words.
+/// Errors are `LC3Error::Custom` messages that start with the offending line number.
+pub fn assemble(source: &str) -> Result<(u16, Vec<u16>), LC3Error> {
+    assemble_program(source).map(|program| (program.origin, program.words))
+}
+
+/// Like `assemble`, but keeps the symbol table and per-word source lines
+pub fn assemble_program(source: &str) -> Result<AssembledProgram, LC3Error> {
+    assemble_source(source, &[])
+}
+
+/// Like `assemble`, with `.IF name`/`.ELSE`/`.ENDIF` blocks kept or dropped
+/// depending on whether `name` is in `defines`
+pub fn assemble_with_defines(source: &str, defines: &[&str]) -> Result<(u16, Vec<u16>), LC3Error> {
+    assemble_source(source, defines).map(|program| (program.origin, program.words))
+}
+
+fn assemble_source(source: &str, defines: &[&str]) -> Result<AssembledProgram, LC3Error> {
+    let mut assembler = Assembler {
+        defines: defines.iter().map(|name| name.to_string()).collect(),
+        ..Assembler::default()
+    };
+
+    for (index, text) in source.lines().enumerate() {
+        assembler.line = index + 1;
+        let ended = assembler
+            .assemble_line(text)
+            .map_err(|msg| LC3Error::Custom(format!("Line {}: {}", index + 1, msg)))?;
+        if ended {
+            break;
+        }
+    }
+
+    assembler.finish()
+}
+
+impl Assembler {
+    fn address(&self) -> u16 {
+        self.origin
+            .unwrap_or(0)
+            .wrapping_add(self.words.len() as u16)
+    }
+
+    fn emit(&mut self, word: u16) -> Result<(), String> {
+        if self.origin.unwrap_or(0) as usize + self.words.len() > u16::MAX as usize {
+            return Err("program extends past xFFFF".to_string());
+        }
+        self.words.push(word);
+        self.lines.push(self.line);
+        self.code.push(false);
+        Ok(())
+    }
+
+    fn emit_instruction(&mut self, word: u16) -> Result<(), String> {
+        self.emit(word)?;
+        if let Some(code) = self.code.last_mut() {
+            *code = true;
+        }
+        Ok(())
+    }
+
+    /// Assemble one line; returns true once `.END` is reached
+    fn assemble_line(&mut self, text: &str) -> Result<bool, String> {
+        let (code, literal) = split_string_literal(strip_comment(text));
+        let mut tokens: Vec<&str> = code
+            .split(|c: char| c.is_whitespace() || c == ',')
+            .filter(|token| !token.is_empty())
+            .collect();
+
+        if let Some(directive) = tokens.first()
+            && self.conditional(directive, &tokens[1..])?
+        {
+            return Ok(false);
+        }
+        if !self.conditions.iter().all(|condition| condition.active) {
+            return Ok(false);
+        }
+
+        if tokens.is_empty() {
+            return match literal {
+                Some(_) => Err("string literal without .STRINGZ".to_string()),
+                None => Ok(false),
+            };
+        }
+
+        if !is_keyword(tokens[0]) {
+            self.define_label(tokens.remove(0))?;
+            if tokens.is_empty() {
+                return Ok(false);
+            }
+        }
+
+        let mnemonic = tokens[0].to_ascii_uppercase();
+        let operands = &tokens[1..];
+
+        if mnemonic == ".ORIG" {
+            if self.origin.is_some() {
+                return Err("duplicate .ORIG".to_string());
+            }
+            expect_operands(&mnemonic, operands, 1)?;
+            self.origin = Some(parse_unsigned(operands[0], 16)?);
+            return Ok(false);
+        }
+        if self.origin.is_none() {
+            return Err(format!("expected .ORIG before '{}'", tokens[0]));
+        }
+
+        match mnemonic.as_str() {
+            ".END" => return Ok(true),
+            ".FILL" => {
+                expect_operands(&mnemonic, operands, 1)?;
+                match parse_number(operands[0]) {
+                    Some(value) if (-32768..=65535).contains(&value) => self.emit(value as u16)?,
+                    Some(value) => {
+                        return Err(format!(".FILL value {} does not fit in 16 bits", value));
+                    }
+                    None => {
+                        self.reference(operands[0], 16)?;
+                        self.emit(0)?;
+                    }
+                }
+            }
+            ".BLKW" => {
+                if operands.is_empty() || operands.len() > 2 {
+                    return Err(format!(
+                        ".BLKW expects 1 or 2 operands, got {}",
+                        operands.len()
+                    ));
+                }
+                let count = parse_unsigned(operands[0], 16)?;
+                let fill = match operands.get(1) {
+                    Some(token) => parse_signed(token, 16)?,
+                    None => 0,
+                };
+                for _ in 0..count {
+                    self.emit(fill)?;
+                }
+            }
+            ".STRINGZ" => {
+                if !operands.is_empty() {
+                    return Err(".STRINGZ expects a quoted string".to_string());
+                }
+                let text =
+                    parse_string_literal(literal.ok_or(".STRINGZ expects a quoted string")?)?;
+                for byte in text.bytes().chain([0]) {
+                    self.emit(byte as u16)?;
+                }
+            }
+            _ => {
+                if literal.is_some() {
+                    return Err(format!("unexpected string literal after {}", mnemonic));
+                }
+                let word = self.encode(&mnemonic, operands)?;
+                self.emit_instruction(word)?;
+            }
+        }
+
+        Ok(false)
+    }
+
+    /// Handle `.IF`/`.ELSE`/`.ENDIF`; returns true if `directive` was one of them
+    fn conditional(&mut self, directive: &str, operands: &[&str]) -> Result<bool, String> {
+        match directive.to_ascii_uppercase().as_str() {
+            ".IF" => {
+                expect_operands(".IF", operands, 1)?;
+                self.conditions.push(Condition {
+                    active: self.defines.iter().any(|name| name == operands[0]),
+                    seen_else: false,
+                });
+            }
+            ".ELSE" => {
+                expect_operands(".ELSE", operands, 0)?;
+                let condition = self.conditions.last_mut().ok_or(".ELSE without .IF")?;
+                if condition.seen_else {
+                    return Err("duplicate .ELSE".to_string());
+                }
+                condition.seen_else = true;
+                condition.active = !condition.active;
+            }
+            ".ENDIF" => {
+                expect_operands(".ENDIF", operands, 0)?;
+                self.conditions.pop().ok_or(".ENDIF without .IF")?;
+            }
+            _ => return Ok(false),
+        }
+        Ok(true)
+    }
+
+    fn define_label(&mut self, token: &str) -> Result<(), String> {
+        let label = token.strip_suffix(':').unwrap_or(token);
+        if !is_valid_label(label) {
+            return Err(format!("unknown instruction or invalid label '{}'", token));
+        }
+        if self.origin.is_none() {
+            return Err(format!("label '{}' before .ORIG", label));
+        }
+        if self
+            .labels
+            .insert(label.to_string(), self.address())
+            .is_some()
+        {
+            return Err(format!("duplicate label '{}'", label));
+        }
+        Ok(())
+    }
+
+    /// Record a label operand for the word about to be emitted
+    fn reference(&mut self, label: &str, bits: u32) -> Result<(), String> {
+        if !is_valid_label(label) {
+            return Err(format!("expected a label or number, got '{}'", label));
+        }
+        self.fixups.push(Fixup {
+            index: self.words.len(),
+            label: label.to_string(),
+            bits,
+            line: self.line,
+        });
+        Ok(())
+    }
+
+    /// A PC-relative operand: a literal offset, or a label patched in later
+    fn pc_offset(&mut self, token: &str, bits: u32) -> Result<u16, String> {
+        if parse_number(token).is_some() {
+            return parse_signed(token, bits);
+        }
+        self.reference(token, bits)?;
+        Ok(0)
+    }
+
+    fn encode(&mut self, mnemonic: &str, operands: &[&str]) -> Result<u16, String> {
+        let op = |opcode: Opcodes| opcode.to_u16() << 12;
+
+        if let Some(vector) = trap_alias(mnemonic) {
+            expect_operands(mnemonic, operands, 0)?;
+            return Ok(op(Opcodes::TRAP) | vector.to_u16());
+        }
+        if let Some(flags) = mnemonic.strip_prefix("BR") {
+            expect_operands(mnemonic, operands, 1)?;
+            let nzp = if flags.is_empty() {
+                0x7
+            } else {
+                parse_condition(flags)?
+            };
+            return Ok(op(Opcodes::BR) | (nzp << 9) | self.pc_offset(operands[0], 9)?);
+        }
+
+        let word = match mnemonic {
+            "ADD" | "AND" => {
+                expect_operands(mnemonic, operands, 3)?;
+                let opcode = if mnemonic == "ADD" {
+                    Opcodes::ADD
+                } else {
+                    Opcodes::AND
+                };
+                let source = match parse_register(operands[2]) {
+                    Ok(sr2) => sr2,
+                    Err(_) => (1 << 5) | parse_signed(operands[2], 5)?,
+                };
+                op(opcode)
+                    | (parse_register(operands[0])? << 9)
+                    | (parse_register(operands[1])? << 6)
+                    | source
+            }
+            "NOT" => {
+                expect_operands(mnemonic, operands, 2)?;
+                op(Opcodes::NOT)
+                    | (parse_register(operands[0])? << 9)
+                    | (parse_register(operands[1])? << 6)
+                    | 0x3F
+            }
+            "LD" | "LDI" | "ST" | "STI" | "LEA" => {
+                expect_operands(mnemonic, operands, 2)?;
+                let opcode = match mnemonic {
+                    "LD" => Opcodes::LD,
+                    "LDI" => Opcodes::LDI,
+                    "ST" => Opcodes::ST,
+                    "STI" => Opcodes::STI,
+                    _ => Opcodes::LEA,
+                };
+                op(opcode) | (parse_register(operands[0])? << 9) | self.pc_offset(operands[1], 9)?
+            }
+            "LDR" | "STR" => {
+                expect_operands(mnemonic, operands, 3)?;
+                let opcode = if mnemonic == "LDR" {
+                    Opcodes::LDR
+                } else {
+                    Opcodes::STR
+                };
+                op(opcode)
+                    | (parse_register(operands[0])? << 9)
+                    | (parse_register(operands[1])? << 6)
+                    | parse_signed(operands[2], 6)?
+            }
+            "JMP" | "JSRR" => {
+                expect_operands(mnemonic, operands, 1)?;
+                let opcode = if mnemonic == "JMP" {
+                    Opcodes::JMP
+                } else {
+                    Opcodes::JSR
+                };
+                op(opcode) | (parse_register(operands[0])? << 6)
+            }
+            "RET" => {
+                expect_operands(mnemonic, operands, 0)?;
+                op(Opcodes::JMP) | (7 << 6)
+            }
+            "JSR" => {
+                expect_operands(mnemonic, operands, 1)?;
+                op(Opcodes::JSR) | (1 << 11) | self.pc_offset(operands[0], 11)?
+            }
+            "TRAP" => {
+                expect_operands(mnemonic, operands, 1)?;
+                op(Opcodes::TRAP) | parse_unsigned(operands[0], 8)?
+            }
+            "RTI" => {
+                expect_operands(mnemonic, operands, 0)?;
+                op(Opcodes::RTI)
+            }
+            _ => return Err(format!("unknown instruction '{}'", mnemonic)),
+        };
+
+        Ok(word)
+    }
+
+    fn finish(self) -> Result<AssembledProgram, LC3Error> {
+        if !self.conditions.is_empty() {
+            return Err(LC3Error::Custom("Missing .ENDIF".to_string()));
+        }
+        let origin = self
+            .origin
+            .ok_or_else(|| LC3Error::Custom("Missing .ORIG directive".to_string()))?;
+        let mut words = self.words;
+
+        for fixup in &self.fixups {
+            let error = |msg: String| LC3Error::Custom(format!("Line {}: {}", fixup.line, msg));
+            let target = *self
+                .labels
+                .get(&fixup.label)
+                .ok_or_else(|| error(format!("undefined label '{}'", fixup.label)))?;
+
+            if fixup.bits == 16 {
+                words[fixup.index] = target;
+                continue;
+            }
+            let next = origin.wrapping_add(fixup.index as u16).wrapping_add(1);
+            let offset = target.wrapping_sub(next) as i16 as i32;
+            let field = fit_signed(offset, fixup.bits).map_err(|_| {
+                error(format!(
+                    "label '{}' is out of range for a {}-bit offset",
+                    fixup.label, fixup.bits
+                ))
+            })?;
+            words[fixup.index] |= field;
+        }
+
+        Ok(AssembledProgram {
+            origin,
+            words,
+            symbols: self.labels.into_iter().collect(),
+            lines: self.lines,
+            code: self.code,
+        })
+    }
+}
+
+impl AssembledProgram {
+    /// Advisory check: different labels that resolve to the same address, usually
+    /// labels on consecutive lines with nothing emitted between them
+    pub fn aliased_labels(&self) -> Vec<String> {
+        let mut by_address: BTreeMap<u16, Vec<&str>> = BTreeMap::new();
+        for (name, &address) in &self.symbols {
+            by_address.entry(address).or_default().push(name);
+        }
+
+        by_address
+            .into_iter()
+            .filter(|(_, names)| names.len() > 1)
+            .map(|(address, names)| {
+                format!(
+                    "labels {} all resolve to x{:04X}",
+                    names.join(", "),
+                    address
+                )
+            })
+            .collect()
+    }
+
+    /// Symbol table in the reference `lc3as` `.sym` text format, ordered by address
+    pub fn to_sym_text(&self) -> String {
+        let mut symbols: Vec<(&String, &u16)> = self.symbols.iter().collect();
+        symbols.sort_by_key(|&(name, address)| (*address, name));
+
+        let mut text = String::from(
+            "// Symbol table\n\
+             // Scope level 0:\n\
+             //\tSymbol Name       Page Address\n\
+             //\t----------------  ------------\n",
+        );
+        for (name, address) in symbols {
+            text.push_str(&format!("//\t{:<16}  {:04X}\n", name, address));
+        }
+        text.push('\n');
+        text
+    }
+
+    /// First and last address holding an instruction, None if the program is all data
+    pub fn code_region(&self) -> Option<(u16, u16)> {
+        self.region(true)
+    }
+
+    /// First and last address holding data (`.FILL`, `.BLKW`, `.STRINGZ`), None if
+    /// the program is all instructions
+    pub fn data_region(&self) -> Option<(u16, u16)> {
+        self.region(false)
+    }
+
+    fn region(&self, code: bool) -> Option<(u16, u16)> {
+        let mut addresses = self
+            .code
+            .iter()
+            .enumerate()
+            .filter(|&(_, &is_code)| is_code == code)
+            .map(|(index, _)| self.origin.wrapping_add(index as u16));
+        let first = addresses.next()?;
+        Some((first, addresses.next_back().unwrap_or(first)))
+    }
+
+    /// Advisory lint: instructions that follow an unconditional BRnzp, JMP/RET or HALT
+    /// with no label on them can never execute. Returns `(line, warning)` pairs.
+    pub fn unreachable_code(&self) -> Vec<(usize, String)> {
+        let labelled: Vec<u16> = self.symbols.values().copied().collect();
+        let mut warnings = Vec::new();
+        let mut dead_after: Option<String> = None;
+
+        for (index, &word) in self.words.iter().enumerate() {
+            let address = self.origin.wrapping_add(index as u16);
+            if labelled.contains(&address) {
+                dead_after = None;
+            }
+            if !self.code[index] {
+                continue;
+            }
+            if let Some(transfer) = &dead_after {
+                warnings.push((
+                    self.lines[index],
+                    format!(
+                        "unreachable instruction '{}' after {}",
+                        disassemble(word, address),
+                        transfer
+                    ),
+                ));
+            } else if is_unconditional_transfer(word) {
+                dead_after = Some(disassemble(word, address));
+            }
+        }
+
+        warnings
+    }
+}
+
+/// BRnzp, JMP (including RET) and HALT never fall through to the next word
+fn is_unconditional_transfer(word: u16) -> bool {
+    match Opcodes::from_u16(extract_opcode(word)) {
+        Some(Opcodes::BR) => word & 0x0E00 == 0x0E00,
+        Some(Opcodes::JMP) => true,
+        Some(Opcodes::TRAP) => word & 0xFF == TrapVectors::HALT.to_u16(),
+        _ => false,
+    }
+}
+
+/// Drop everything from the first `;` that is not inside a string literal
+fn strip_comment(text: &str) -> &str {
+    let mut in_string = false;
+    let mut escaped = false;
+
+    for (i, c) in text.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if in_string => escaped = true,
+            '"' => in_string = !in_string,
+            ';' if !in_string => return &text[..i],
+            _ => {}
+        }
+    }
+    text
+}
+
+fn split_string_literal(text: &str) -> (&str, Option<&str>) {
+    match text.find('"') {
+        Some(i) => (&text[..i], Some(text[i..].trim_end())),
+        None => (text, None),
+    }
+}
+
+fn parse_string_literal(literal: &str) -> Result<String, String> {
+    let inner = literal
+        .strip_prefix('"')
+        .and_then(|rest| rest.strip_suffix('"'))
+        .filter(|_| literal.len() >= 2)
+        .ok_or_else(|| format!("unterminated string {}", literal))?;
+
+    let mut text = String::new();
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            text.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => text.push('\n'),
+            Some('t') => text.push('\t'),
+            Some('r') => text.push('\r'),
+            Some('0') => text.push('\0'),
+            Some(c @ ('\\' | '"')) => text.push(c),
+            Some(c) => return Err(format!("unknown escape '\\{}'", c)),
+            None => return Err("string ends with a lone backslash".to_string()),
+        }
+    }
+    Ok(text)
+}
+
+fn is_keyword(token: &str) -> bool {
+    let upper = token.to_ascii_uppercase();
+    upper.starts_with('.')
+        || trap_alias(&upper).is_some()
+        || upper
+            .strip_prefix("BR")
+            .is_some_and(|flags| parse_condition(flags).is_ok() || flags.is_empty())
+        || matches!(
+            upper.as_str(),
+            "ADD"
+                | "AND"
+                | "NOT"
+                | "LD"
+                | "LDI"
+                | "LDR"
+                | "LEA"
+                | "ST"
+                | "STI"
+                | "STR"
+                | "JMP"
+                | "RET"
+                | "JSR"
+                | "JSRR"
+                | "TRAP"
+                | "RTI"
+        )
+}
+
+fn trap_alias(mnemonic: &str) -> Option<TrapVectors> {
+    match mnemonic {
+        "GETC" => Some(TrapVectors::GETC),
+        "OUT" => Some(TrapVectors::OUT),
+        "PUTS" => Some(TrapVectors::PUTS),
+        "IN" => Some(TrapVectors::IN),
+        "PUTSP" => Some(TrapVectors::PUTSP),
+        "HALT" => Some(TrapVectors::HALT),
+        _ => None,
+    }
+}
+
+fn is_valid_label(label: &str) -> bool {
+    let mut chars = label.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+        && parse_register(label).is_err()
+}
+
+/// `n`, `z` and `p` suffix letters of a BR mnemonic (already upper-cased)
+fn parse_condition(flags: &str) -> Result<u16, String> {
+    let mut nzp = 0;
+    for c in flags.chars() {
+        let bit = match c {
+            'N' => 0x4,
+            'Z' => 0x2,
+            'P' => 0x1,
+            _ => return Err(format!("invalid branch condition 'BR{}'", flags)),
+        };
+        if nzp & bit != 0 {
+            return Err(format!("repeated branch condition in 'BR{}'", flags));
+        }
+        nzp |= bit;
+    }
+    Ok(nzp)
+}
+
+fn parse_register(token: &str) -> Result<u16, String> {
+    match token.as_bytes() {
+        [b'R' | b'r', digit @ b'0'..=b'7'] => Ok((digit - b'0') as u16),
+        _ => Err(format!("expected a register R0-R7, got '{}'", token)),
+    }
+}
+
+/// `#decimal`, plain decimal, or `x`/`0x` hex, each optionally negative
+fn parse_number(token: &str) -> Option<i32> {
+    if let Some(decimal) = token.strip_prefix('#') {
+        return decimal.parse().ok();
+    }
+    let hex = token
+        .strip_prefix("0x")
+        .or_else(|| token.strip_prefix("0X"))
+        .or_else(|| token.strip_prefix(['x', 'X']));
+    match hex {
+        Some(digits) => i32::from_str_radix(digits, 16).ok(),
+        None => token.parse().ok(),
+    }
+}
+
+fn parse_signed(token: &str, bits: u32) -> Result<u16, String> {
+    let value = parse_number(token).ok_or_else(|| format!("expected a number, got '{}'", token))?;
+    fit_signed(value, bits)
+}
+
+fn parse_unsigned(token: &str, bits: u32) -> Result<u16, String> {
+    let value = parse_number(token).ok_or_else(|| format!("expected a number, got '{}'", token))?;
+    if value < 0 || value >= 1 << bits {
+        return Err(format!("{} does not fit in {} unsigned bits", token, bits));
+    }
+    Ok(value as u16)
+}
+
+/// Two's-complement `value` in a `bits`-wide field; 16-bit fields also take unsigned values
+fn fit_signed(value: i32, bits: u32) -> Result<u16, String> {
+    let min = -(1 << (bits - 1));
+    let max = if bits == 16 {
+        u16::MAX as i32
+    } else {
+        (1 << (bits - 1)) - 1
+    };
+    if value < min || value > max {
+        return Err(format!("{} does not fit in a {}-bit field", value, bits));
+    }
+    Ok((value as u16) & (u16::MAX >> (16 - bits)))
+}
+
+fn expect_operands(mnemonic: &str, operands: &[&str], count: usize) -> Result<(), String> {
+    if operands.len() != count {
+        return Err(format!(
+            "{} expects {} operand(s), got {}",
+            mnemonic,
+            count,
+            operands.len()
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unreachable_code_flags_an_unlabelled_instruction_after_halt() {
+        let program = assemble_program(
+            ".ORIG x3000
+                ADD R0, R0, #1
+                HALT
+                ADD R0, R0, #2
+            LIVE ADD R0, R0, #3
+                BRnzp LIVE
+            DATA .FILL #4
+            .END",
+        )
+        .unwrap();
+
+        assert_eq!(
+            program.unreachable_code(),
+            vec![(
+                4,
+                "unreachable instruction 'ADD R0, R0, #2' after HALT".to_string()
+            )]
+        );
+    }
+
+    #[test]
+    fn sym_text_lists_labels_in_address_order() {
+        let program = assemble_program(
+            ".ORIG x3000
+            START LD R0, VALUE
+                HALT
+            VALUE .FILL #9
+            .END",
+        )
+        .unwrap();
+
+        assert_eq!(
+            program.to_sym_text(),
+            "// Symbol table\n\
+             // Scope level 0:\n\
+             //\tSymbol Name       Page Address\n\
+             //\t----------------  ------------\n\
+             //\tSTART             3000\n\
+             //\tVALUE             3002\n\
+             \n"
+        );
+    }
+
+    #[test]
+    fn aliased_labels_reports_labels_sharing_an_address() {
+        let program = assemble_program(
+            ".ORIG x3000
+            START ADD R0, R0, #1
+            DONE
+            FINISH HALT
+            .END",
+        )
+        .unwrap();
+
+        assert_eq!(
+            program.aliased_labels(),
+            vec!["labels DONE, FINISH all resolve to x3001".to_string()]
+        );
+    }
+
+    const LOOP_PROGRAM: &str = ".ORIG x3000
+        LOOP ADD R1, R1, #-1
+            ADD R2, R2, #1
+            ADD R3, R3, #1
+            BRz DONE
+            ADD R4, R4, #1
+            BRnzp LOOP
+            ADD R0, R0, #0
+        DONE HALT
+        .END";
+
+    #[test]
+    fn backward_branch_gets_a_negative_offset() {
+        let (_, words) = assemble(LOOP_PROGRAM).unwrap();
+
+        // x3005 BRnzp LOOP: x3000 - x3006 = -6
+        assert_eq!(words[5], 0x0FFA);
+    }
+
+    #[test]
+    fn forward_branch_gets_a_positive_offset() {
+        let (_, words) = assemble(LOOP_PROGRAM).unwrap();
+
+        // x3003 BRz DONE: x3007 - x3004 = 3
+        assert_eq!(words[3], 0x0403);
+    }
+
+    #[test]
+    fn defines_select_conditional_blocks() {
+        let source = ".ORIG x3000
+            .IF DEBUG
+                ADD R0, R0, #1
+            .ELSE
+                ADD R0, R0, #2
+            .ENDIF
+                HALT
+            .END";
+
+        assert_eq!(
+            assemble_with_defines(source, &["DEBUG"]).unwrap(),
+            (0x3000, vec![0x1021, 0xF025])
+        );
+        assert_eq!(
+            assemble_with_defines(source, &[]).unwrap(),
+            (0x3000, vec![0x1022, 0xF025])
+        );
+    }
+
+    #[test]
+    fn unbalanced_conditionals_are_errors() {
+        assert_eq!(
+            assemble(".ORIG x3000\n.IF X\nHALT\n.END").unwrap_err(),
+            LC3Error::Custom("Missing .ENDIF".to_string())
+        );
+        assert_eq!(
+            assemble(".ORIG x3000\n.ENDIF\n.END").unwrap_err(),
+            LC3Error::Custom("Line 2: .ENDIF without .IF".to_string())
+        );
+    }
+
+    #[test]
+    fn blkw_fills_with_an_optional_value() {
+        let (origin, words) = assemble(
+            ".ORIG x3000
+            .BLKW 3, x7
+            .BLKW 2
+            .BLKW 1, #-1
+            .END",
+        )
+        .unwrap();
+
+        assert_eq!(origin, 0x3000);
+        assert_eq!(words, vec![7, 7, 7, 0, 0, 0xFFFF]);
+    }
+
+    #[test]
+    fn blkw_rejects_extra_operands() {
+        let err = assemble(".ORIG x3000\n.BLKW 1, 2, 3\n.END").unwrap_err();
+
+        assert_eq!(
+            err,
+            LC3Error::Custom("Line 2: .BLKW expects 1 or 2 operands, got 3".to_string())
+        );
+    }
+
+    #[test]
+    fn code_and_data_regions_follow_what_was_emitted() {
+        let program = assemble_program(
+            ".ORIG x3000
+                LEA R0, TEXT
+                PUTS
+                HALT
+            TEXT .STRINGZ \"hi\"
+            COUNT .BLKW 2
+            .END",
+        )
+        .unwrap();
+
+        assert_eq!(program.code_region(), Some((0x3000, 0x3002)));
+        assert_eq!(program.data_region(), Some((0x3003, 0x3007)));
+
+        let data_only = assemble_program(".ORIG x4000\n.FILL #1\n.END").unwrap();
+        assert_eq!(data_only.code_region(), None);
+        assert_eq!(data_only.data_region(), Some((0x4000, 0x4000)));
+    }
+}
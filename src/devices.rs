@@ -0,0 +1,105 @@
+use std::collections::VecDeque;
+
+use crate::io::Io;
+use crate::types::{KBSR_INTERRUPT_ENABLE, KBSR_READY};
+
+/// Keyboard behind the memory-mapped KBSR/KBDR registers.
+/// Keys come from `press` or, once those run out, from an optional input source.
+#[derive(Default)]
+pub struct Keyboard {
+    source: Option<Box<dyn Io>>,
+    keys: VecDeque<u8>,
+    interrupt_enabled: bool,
+}
+
+impl Keyboard {
+    /// A keyboard fed only by `press`
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A keyboard that polls `source` when no pressed key is waiting.
+    /// A blocking source (like `StdIo`) makes KBSR reads wait for input.
+    pub fn with_source(source: Box<dyn Io>) -> Self {
+        Self {
+            source: Some(source),
+            keys: VecDeque::new(),
+            interrupt_enabled: false,
+        }
+    }
+
+    /// Queue a keypress as if it had just been typed
+    pub fn press(&mut self, key: u8) {
+        self.keys.push_back(key);
+    }
+
+    /// Whether a key is waiting, polling the source once if none is queued
+    pub fn ready(&mut self) -> bool {
+        if self.keys.is_empty()
+            && let Some(key) = self.source.as_mut().and_then(|source| source.read_byte())
+        {
+            self.keys.push_back(key);
+        }
+        !self.keys.is_empty()
+    }
+
+    /// Take the waiting key, clearing the ready bit if it was the last one
+    pub fn take(&mut self) -> Option<u8> {
+        self.ready();
+        self.keys.pop_front()
+    }
+
+    /// KBSR as the program sees it: ready bit plus interrupt-enable bit
+    pub fn status(&mut self) -> u16 {
+        let ready = if self.ready() { KBSR_READY } else { 0 };
+        let enabled = if self.interrupt_enabled {
+            KBSR_INTERRUPT_ENABLE
+        } else {
+            0
+        };
+        ready | enabled
+    }
+
+    /// Handle a program store to KBSR; only the interrupt-enable bit is writable
+    pub fn set_status(&mut self, value: u16) {
+        self.interrupt_enabled = value & KBSR_INTERRUPT_ENABLE != 0;
+    }
+
+    /// Whether interrupts are enabled and a key is waiting
+    pub fn interrupt_pending(&mut self) -> bool {
+        self.interrupt_enabled && self.ready()
+    }
+}
+
+impl std::fmt::Debug for Keyboard {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Keyboard")
+            .field("has_source", &self.source.is_some())
+            .field("keys", &self.keys)
+            .field("interrupt_enabled", &self.interrupt_enabled)
+            .finish()
+    }
+}
+
+/// Display behind the memory-mapped DSR/DDR registers. It is always ready;
+/// characters stored to DDR wait here until the VM forwards them to its `Io`.
+#[derive(Debug, Default)]
+pub struct Display {
+    pending: Vec<u8>,
+}
+
+impl Display {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Handle a program store to DDR
+    pub fn write(&mut self, byte: u8) {
+        self.pending.push(byte);
+    }
+
+    /// Characters written since the last call
+    pub fn take_output(&mut self) -> Vec<u8> {
+        std::mem::take(&mut self.pending)
+    }
+}
@@ -0,0 +1,119 @@
+//! Turns single instruction words back into assembly text.
+//! PC-relative operands are shown as the absolute address they resolve to.
+
+use crate::instructions::branch_target;
+use crate::types::{
+    Opcodes, TrapVectors, extract_dr, extract_imm5, extract_imm5_flag, extract_offset6,
+    extract_opcode, extract_pc_offset9, extract_sr1, extract_sr2, extract_trap_vector,
+    sign_extend_imm5, sign_extend_offset6, sign_extend_pc_offset9,
+};
+
+/// Disassemble `instruction`, located at address `pc`, e.g. `ADD R1, R2, #-3` or
+/// `BRnzp x3010`. RES words render as `.FILL xNNNN`; a BR that tests no flags is `NOP`.
+pub fn disassemble(instruction: u16, pc: u16) -> String {
+    let Some(opcode) = Opcodes::from_u16(extract_opcode(instruction)) else {
+        return fill(instruction);
+    };
+    let dr = extract_dr(instruction);
+    let sr1 = extract_sr1(instruction);
+    let pc_relative = || {
+        let offset = sign_extend_pc_offset9(extract_pc_offset9(instruction));
+        pc.wrapping_add(1).wrapping_add(offset)
+    };
+
+    match opcode {
+        Opcodes::BR => {
+            let nzp = (instruction >> 9) & 0x7;
+            if nzp == 0 {
+                return "NOP".to_string();
+            }
+            let flags: String = [(0x4, 'n'), (0x2, 'z'), (0x1, 'p')]
+                .iter()
+                .filter(|&&(bit, _)| nzp & bit != 0)
+                .map(|&(_, flag)| flag)
+                .collect();
+            format!(
+                "BR{} x{:04X}",
+                flags,
+                branch_target(instruction, pc).unwrap_or(0)
+            )
+        }
+        Opcodes::ADD | Opcodes::AND => {
+            let source = if extract_imm5_flag(instruction) {
+                format!("#{}", sign_extend_imm5(extract_imm5(instruction)) as i16)
+            } else {
+                format!("R{}", extract_sr2(instruction))
+            };
+            format!("{} R{}, R{}, {}", opcode.to_string(), dr, sr1, source)
+        }
+        Opcodes::NOT => format!("NOT R{}, R{}", dr, sr1),
+        Opcodes::LD | Opcodes::LDI | Opcodes::ST | Opcodes::STI | Opcodes::LEA => {
+            format!("{} R{}, x{:04X}", opcode.to_string(), dr, pc_relative())
+        }
+        Opcodes::LDR | Opcodes::STR => format!(
+            "{} R{}, R{}, #{}",
+            opcode.to_string(),
+            dr,
+            sr1,
+            sign_extend_offset6(extract_offset6(instruction)) as i16
+        ),
+        Opcodes::JMP if sr1 == 7 => "RET".to_string(),
+        Opcodes::JMP => format!("JMP R{}", sr1),
+        Opcodes::JSR => match branch_target(instruction, pc) {
+            Some(target) => format!("JSR x{:04X}", target),
+            None => format!("JSRR R{}", sr1),
+        },
+        Opcodes::TRAP => {
+            let vector = extract_trap_vector(instruction);
+            match TrapVectors::from_u16(vector) {
+                Some(trap) => trap.to_string().to_string(),
+                None => format!("TRAP x{:02X}", vector),
+            }
+        }
+        Opcodes::RTI => "RTI".to_string(),
+        Opcodes::RES => fill(instruction),
+    }
+}
+
+fn fill(word: u16) -> String {
+    format!(".FILL x{:04X}", word)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn operate_instructions() {
+        assert_eq!(disassemble(0x12BD, 0x3000), "ADD R1, R2, #-3");
+        assert_eq!(disassemble(0x1283, 0x3000), "ADD R1, R2, R3");
+        assert_eq!(disassemble(0x5020, 0x3000), "AND R0, R0, #0");
+        assert_eq!(disassemble(0x927F, 0x3000), "NOT R1, R1");
+    }
+
+    #[test]
+    fn pc_relative_operands_show_the_target_address() {
+        assert_eq!(disassemble(0x0E10, 0x3000), "BRnzp x3011");
+        assert_eq!(disassemble(0x05FE, 0x3000), "BRz x2FFF");
+        assert_eq!(disassemble(0x2002, 0x3000), "LD R0, x3003");
+        assert_eq!(disassemble(0xE3FF, 0x3000), "LEA R1, x3000");
+        assert_eq!(disassemble(0x4803, 0x3000), "JSR x3004");
+    }
+
+    #[test]
+    fn register_and_base_relative_forms() {
+        assert_eq!(disassemble(0x647E, 0x3000), "LDR R2, R1, #-2");
+        assert_eq!(disassemble(0xC1C0, 0x3000), "RET");
+        assert_eq!(disassemble(0xC080, 0x3000), "JMP R2");
+        assert_eq!(disassemble(0x4080, 0x3000), "JSRR R2");
+        assert_eq!(disassemble(0x8000, 0x3000), "RTI");
+    }
+
+    #[test]
+    fn traps_nops_and_reserved_words() {
+        assert_eq!(disassemble(0xF025, 0x3000), "HALT");
+        assert_eq!(disassemble(0xF030, 0x3000), "TRAP x30");
+        assert_eq!(disassemble(0x0000, 0x3000), "NOP");
+        assert_eq!(disassemble(0xD123, 0x3000), ".FILL xD123");
+    }
+}
@@ -1,9 +1,11 @@
-use crate::memory::Memory;
+use crate::disassembler::disassemble;
+use crate::io::Io;
+use crate::memory::{Memory, parse_object};
 use crate::registers::RegisterFile;
 use crate::types::{
-    Flags, Opcodes, Registers, TrapVectors, extract_dr, extract_imm5, extract_imm5_flag,
-    extract_offset6, extract_pc_offset9, extract_pc_offset11, extract_sr1, extract_sr2,
-    extract_trap_vector, sign_extend_imm5, sign_extend_offset6, sign_extend_pc_offset9,
+    AddSource, DEFAULT_MAX_STRING_LENGTH, Flags, Instruction, LC3Error, Opcodes, Registers,
+    TrapVectors, decode, extract_offset6, extract_opcode, extract_pc_offset9, extract_pc_offset11,
+    extract_sr1, extract_trap_vector, sign_extend_offset6, sign_extend_pc_offset9,
     sign_extend_pc_offset11,
 };
 
@@ -11,18 +13,48 @@ use crate::types::{
 pub enum ExecutionResult {
     Continue,
     Halt,
+    /// A privilege or access-control exception blocked the instruction before it ran;
+    /// execution carries on in the exception handler
+    Exception,
     Error(String),
 }
 
+/// How the VM treats the reserved RES opcode
+#[derive(Debug, Clone, Copy, Default)]
+pub enum ResPolicy {
+    /// Stop with an error (standard behavior)
+    #[default]
+    Error,
+    /// Treat RES as a no-op and carry on with the next instruction
+    Nop,
+    /// Hand the raw instruction to a user-supplied handler
+    Custom(fn(u16, &mut Memory, &mut RegisterFile) -> ExecutionResult),
+}
+
 pub struct InstructionExecutor;
 
 impl InstructionExecutor {
+    /// Sign-extend the low `bit_count` bits of `instr`.
+    /// Build with the `trace-signext` feature to print each step of the calculation
     pub fn sign_extend(instr: u16, bit_count: usize) -> u16 {
+        let sign_bit = (instr >> (bit_count - 1)) & 1;
+        let result = if sign_bit == 1 {
+            instr | (0xFFFF << bit_count)
+        } else {
+            instr
+        };
+
+        #[cfg(feature = "trace-signext")]
+        Self::trace_sign_extend(instr, bit_count, sign_bit, result);
+
+        result
+    }
+
+    #[cfg(feature = "trace-signext")]
+    fn trace_sign_extend(instr: u16, bit_count: usize, sign_bit: u16, result: u16) {
         println!("=== Sign Extend Debug ===");
         println!("Input: instr = 0x{:04X} ({})", instr, instr);
         println!("Bit count: {}", bit_count);
-
-        let sign_bit = (instr >> (bit_count - 1)) & 1;
         println!(
             "Sign bit check: (instr >> (bit_count - 1)) & 1 = (0x{:04X} >> {}) & 1 = 0x{:04X} & 1 = {}",
             instr,
@@ -31,16 +63,11 @@ impl InstructionExecutor {
             sign_bit
         );
 
-        let mut result = instr;
-
         if sign_bit == 1 {
+            let mask: u16 = 0xFFFF << bit_count;
             println!("Sign bit is set (negative number)");
-            println!("Before extension: 0x{:04X} ({})", result, result);
-
-            let mask = 0xFFFF << bit_count;
+            println!("Before extension: 0x{:04X} ({})", instr, instr);
             println!("Extension mask: 0xFFFF << {} = 0x{:04X}", bit_count, mask);
-
-            result |= mask;
             println!(
                 "After extension: 0x{:04X} | 0x{:04X} = 0x{:04X} ({})",
                 instr, mask, result, result as i16
@@ -50,113 +77,165 @@ impl InstructionExecutor {
         }
 
         println!("Final result: 0x{:04X} ({})", result, result as i16);
-
-        result
     }
 
+    /// Decode `instruction` and execute it; the thin raw-word wrapper around `execute_decoded`
     pub fn execute_instruction(
         instruction: u16,
         memory: &mut Memory,
         registers: &mut RegisterFile,
+        io: &mut dyn Io,
     ) -> ExecutionResult {
-        let opcode = (instruction >> 12) as u16;
-
-        match Opcodes::from_u16(opcode) {
-            Some(Opcodes::BR) => Self::execute_br(instruction, registers),
-            Some(Opcodes::ADD) => Self::execute_add(instruction, registers),
-            Some(Opcodes::LD) => Self::execute_ld(instruction, memory, registers),
-            Some(Opcodes::ST) => Self::execute_st(instruction, memory, registers),
-            Some(Opcodes::JSR) => Self::execute_jsr(instruction, registers),
-            Some(Opcodes::AND) => Self::execute_and(instruction, registers),
-            Some(Opcodes::LDR) => Self::execute_ldr(instruction, memory, registers),
-            Some(Opcodes::STR) => Self::execute_str(instruction, memory, registers),
-            Some(Opcodes::RTI) => {
-                ExecutionResult::Error("RTI instruction not implemented".to_string())
+        match decode(instruction) {
+            Ok(decoded) => Self::execute_decoded(decoded, memory, registers, io),
+            Err(LC3Error::InvalidOpcode(_)) => {
+                ExecutionResult::Error("RES instruction is reserved".to_string())
             }
-            Some(Opcodes::NOT) => Self::execute_not(instruction, registers),
-            Some(Opcodes::LDI) => Self::execute_ldi(instruction, memory, registers),
-            Some(Opcodes::STI) => Self::execute_sti(instruction, memory, registers),
-            Some(Opcodes::JMP) => Self::execute_jmp(instruction, registers),
-            Some(Opcodes::RES) => ExecutionResult::Error("RES instruction is reserved".to_string()),
-            Some(Opcodes::LEA) => Self::execute_lea(instruction, registers),
-            Some(Opcodes::TRAP) => Self::execute_trap(instruction, memory, registers),
-            None => ExecutionResult::Error(format!("Unknown opcode: {}", opcode)),
+            Err(e) => ExecutionResult::Error(e.to_string()),
+        }
+    }
+
+    /// Execute an already-decoded instruction. Operand registers must be R0-R7;
+    /// anything else surfaces as an `InvalidRegister` error instead of being ignored
+    pub fn execute_decoded(
+        instruction: Instruction,
+        memory: &mut Memory,
+        registers: &mut RegisterFile,
+        io: &mut dyn Io,
+    ) -> ExecutionResult {
+        let outcome = match instruction {
+            Instruction::Br { n, z, p, offset } => Self::execute_br(n, z, p, offset, registers),
+            Instruction::Add { dr, sr1, src2 } => Self::execute_add(dr, sr1, src2, registers),
+            Instruction::Ld { dr, offset } => Self::execute_ld(dr, offset, memory, registers),
+            Instruction::St { sr, offset } => Self::execute_st(sr, offset, memory, registers),
+            Instruction::Jsr { offset } => Self::execute_jsr(offset, registers),
+            Instruction::Jsrr { base } => Self::execute_jsrr(base, registers),
+            Instruction::And { dr, sr1, src2 } => Self::execute_and(dr, sr1, src2, registers),
+            Instruction::Ldr { dr, base, offset6 } => {
+                Self::execute_ldr(dr, base, offset6, memory, registers)
+            }
+            Instruction::Str { sr, base, offset6 } => {
+                Self::execute_str(sr, base, offset6, memory, registers)
+            }
+            Instruction::Rti => Ok(ExecutionResult::Error(
+                "RTI needs processor state; run it through LC3VM".to_string(),
+            )),
+            Instruction::Not { dr, sr } => Self::execute_not(dr, sr, registers),
+            Instruction::Ldi { dr, offset } => Self::execute_ldi(dr, offset, memory, registers),
+            Instruction::Sti { sr, offset } => Self::execute_sti(sr, offset, memory, registers),
+            Instruction::Jmp { base } => Self::execute_jmp(base, registers),
+            Instruction::Lea { dr, offset } => Self::execute_lea(dr, offset, registers),
+            Instruction::Trap { vector } => Ok(Self::execute_trap(vector, memory, registers, io)),
+        };
+
+        outcome.unwrap_or_else(|e| ExecutionResult::Error(e.to_string()))
+    }
+
+    /// Value of a general-purpose operand register
+    fn read_operand(registers: &RegisterFile, reg: Registers) -> Result<u16, LC3Error> {
+        let reg = Registers::try_from_field(reg as u16)?;
+        registers
+            .read(reg)
+            .ok_or(LC3Error::InvalidRegister(reg as u16))
+    }
+
+    fn write_operand(
+        registers: &mut RegisterFile,
+        reg: Registers,
+        value: u16,
+    ) -> Result<(), LC3Error> {
+        registers.write(Registers::try_from_field(reg as u16)?, value)
+    }
+
+    /// Write a loaded or computed value and set the condition codes from it
+    fn write_result(
+        registers: &mut RegisterFile,
+        dr: Registers,
+        value: u16,
+    ) -> Result<ExecutionResult, LC3Error> {
+        Self::write_operand(registers, dr, value)?;
+        registers.update_condition_code(value)?;
+        Ok(ExecutionResult::Continue)
+    }
+
+    /// Value of the second ADD/AND operand
+    fn source_value(src2: AddSource, registers: &RegisterFile) -> Result<u16, LC3Error> {
+        match src2 {
+            AddSource::Register(sr2) => Self::read_operand(registers, sr2),
+            AddSource::Immediate(imm5) => Ok(imm5 as u16),
         }
     }
 
-    fn execute_br(instruction: u16, registers: &mut RegisterFile) -> ExecutionResult {
-        let pc_offset9 = extract_pc_offset9(instruction);
-        let nzp = (instruction >> 9) & 0x7;
+    /// Address `offset` words from the already-incremented PC
+    fn pc_relative(offset: i16, registers: &RegisterFile) -> u16 {
+        registers.get_pc().wrapping_add(offset as u16)
+    }
 
-        let should_branch = (nzp & 0x4 != 0 && registers.is_flag_set(Flags::NEG))
-            || (nzp & 0x2 != 0 && registers.is_flag_set(Flags::ZRO))
-            || (nzp & 0x1 != 0 && registers.is_flag_set(Flags::POS));
+    fn execute_br(
+        n: bool,
+        z: bool,
+        p: bool,
+        offset: i16,
+        registers: &mut RegisterFile,
+    ) -> Result<ExecutionResult, LC3Error> {
+        let should_branch = (n && registers.is_flag_set(Flags::NEG))
+            || (z && registers.is_flag_set(Flags::ZRO))
+            || (p && registers.is_flag_set(Flags::POS));
 
         if should_branch {
-            let pc = registers.get_pc();
-            let offset = sign_extend_pc_offset9(pc_offset9);
-            let _ = registers.set_pc(pc + offset);
+            registers.set_pc(Self::pc_relative(offset, registers))?;
         }
 
-        ExecutionResult::Continue
+        Ok(ExecutionResult::Continue)
     }
     //=== Execute ADD instruction ===
-    fn execute_add(instruction: u16, registers: &mut RegisterFile) -> ExecutionResult {
-        let dr = extract_dr(instruction);
-        let sr1 = extract_sr1(instruction);
-        let imm5_flag = extract_imm5_flag(instruction);
-
-        let sr1_value = registers.read(Registers::from(sr1)).unwrap_or(0);
-        let result = if imm5_flag {
-            let imm5 = extract_imm5(instruction);
-            let imm5_value = sign_extend_imm5(imm5);
-            sr1_value.wrapping_add(imm5_value)
-        } else {
-            let sr2 = extract_sr2(instruction);
-            let sr2_value = registers.read(Registers::from(sr2)).unwrap_or(0);
-            sr1_value.wrapping_add(sr2_value)
-        };
-
-        let _ = registers.write(Registers::from(dr), result);
-        let _ = registers.update_condition_code(result);
+    fn execute_add(
+        dr: Registers,
+        sr1: Registers,
+        src2: AddSource,
+        registers: &mut RegisterFile,
+    ) -> Result<ExecutionResult, LC3Error> {
+        let sr1_value = Self::read_operand(registers, sr1)?;
+        let result = sr1_value.wrapping_add(Self::source_value(src2, registers)?);
 
-        ExecutionResult::Continue
+        Self::write_result(registers, dr, result)
     }
 
     //=== Load a value from memory into a register ===
     fn execute_ld(
-        instruction: u16,
+        dr: Registers,
+        offset: i16,
         memory: &Memory,
         registers: &mut RegisterFile,
-    ) -> ExecutionResult {
-        let dr = extract_dr(instruction);
-        let pc_offset9 = extract_pc_offset9(instruction);
-
-        let pc = registers.get_pc();
-        let address = pc + sign_extend_pc_offset9(pc_offset9);
+    ) -> Result<ExecutionResult, LC3Error> {
+        let address = Self::pc_relative(offset, registers);
 
         match memory.read(address) {
-            Some(value) => {
-                let _ = registers.write(Registers::from(dr), value);
-                let _ = registers.update_condition_code(value);
-                ExecutionResult::Continue
-            }
-            None => ExecutionResult::Error("Memory read out of bounds".to_string()),
+            Some(value) => Self::write_result(registers, dr, value),
+            None => Ok(ExecutionResult::Error(
+                "Memory read out of bounds".to_string(),
+            )),
         }
     }
 
     //=== Store a register value to memory ===
     fn execute_st(
-        instruction: u16,
+        sr: Registers,
+        offset: i16,
         memory: &mut Memory,
         registers: &mut RegisterFile,
-    ) -> ExecutionResult {
-        let sr = extract_dr(instruction);
-        let pc_offset9 = extract_pc_offset9(instruction);
+    ) -> Result<ExecutionResult, LC3Error> {
+        let address = Self::pc_relative(offset, registers);
+        let value = Self::read_operand(registers, sr)?;
 
-        let pc = registers.get_pc();
-        let address = pc + sign_extend_pc_offset9(pc_offset9);
-        let value = registers.read(Registers::from(sr)).unwrap_or(0);
+        Ok(Self::store(memory, address, value))
+    }
+
+    /// Write `value` to `address` unless it is read-only or out of range
+    fn store(memory: &mut Memory, address: u16, value: u16) -> ExecutionResult {
+        if memory.is_readonly(address) {
+            return ExecutionResult::Error("write to read-only memory".to_string());
+        }
 
         match memory.write(address, value) {
             Ok(_) => ExecutionResult::Continue,
@@ -165,204 +244,801 @@ impl InstructionExecutor {
     }
 
     //=== Save PC and jump to subroutine ====
-    fn execute_jsr(instruction: u16, registers: &mut RegisterFile) -> ExecutionResult {
+    fn execute_jsr(offset: i16, registers: &mut RegisterFile) -> Result<ExecutionResult, LC3Error> {
         let pc = registers.get_pc();
-        let _ = registers.write(Registers::R7, pc);
+        registers.write(Registers::R7, pc)?;
+        registers.set_pc(pc.wrapping_add(offset as u16))?;
 
-        if (instruction & 0x800) != 0 {
-            let pc_offset11 = extract_pc_offset11(instruction);
-            let offset = sign_extend_pc_offset11(pc_offset11);
-            let _ = registers.set_pc(pc + offset);
-        } else {
-            let base_reg = extract_sr1(instruction);
-            let base_value = registers.read(Registers::from(base_reg)).unwrap_or(0);
-            let _ = registers.set_pc(base_value);
-        }
+        Ok(ExecutionResult::Continue)
+    }
 
-        ExecutionResult::Continue
+    //=== Save PC and jump to the subroutine address held in a register ====
+    fn execute_jsrr(
+        base: Registers,
+        registers: &mut RegisterFile,
+    ) -> Result<ExecutionResult, LC3Error> {
+        // Read the base first: JSRR R7 jumps to the old R7
+        let target = Self::read_operand(registers, base)?;
+        let pc = registers.get_pc();
+        registers.write(Registers::R7, pc)?;
+        registers.set_pc(target)?;
+
+        Ok(ExecutionResult::Continue)
     }
 
     //==== Perform bitwise AND operation ====
-    fn execute_and(instruction: u16, registers: &mut RegisterFile) -> ExecutionResult {
-        let dr = extract_dr(instruction);
-        let sr1 = extract_sr1(instruction);
-        let imm5_flag = extract_imm5_flag(instruction);
-
-        let sr1_value = registers.read(Registers::from(sr1)).unwrap_or(0);
-        let result = if imm5_flag {
-            let imm5 = extract_imm5(instruction);
-            let imm5_value = sign_extend_imm5(imm5);
-            sr1_value & imm5_value
-        } else {
-            let sr2 = extract_sr2(instruction);
-            let sr2_value = registers.read(Registers::from(sr2)).unwrap_or(0);
-            sr1_value & sr2_value
-        };
-
-        let _ = registers.write(Registers::from(dr), result);
-        let _ = registers.update_condition_code(result);
+    fn execute_and(
+        dr: Registers,
+        sr1: Registers,
+        src2: AddSource,
+        registers: &mut RegisterFile,
+    ) -> Result<ExecutionResult, LC3Error> {
+        let sr1_value = Self::read_operand(registers, sr1)?;
+        let result = sr1_value & Self::source_value(src2, registers)?;
 
-        ExecutionResult::Continue
+        Self::write_result(registers, dr, result)
     }
 
     fn execute_ldr(
-        instruction: u16,
+        dr: Registers,
+        base: Registers,
+        offset6: i16,
         memory: &Memory,
         registers: &mut RegisterFile,
-    ) -> ExecutionResult {
-        let dr = extract_dr(instruction);
-        let base_reg = extract_sr1(instruction);
-        let offset6 = extract_offset6(instruction);
-
-        let base_value = registers.read(Registers::from(base_reg)).unwrap_or(0);
-        let offset = sign_extend_offset6(offset6);
-        let address = base_value + offset;
+    ) -> Result<ExecutionResult, LC3Error> {
+        let base_value = Self::read_operand(registers, base)?;
+        let address = base_value.wrapping_add(offset6 as u16);
 
         match memory.read(address) {
-            Some(value) => {
-                let _ = registers.write(Registers::from(dr), value);
-                let _ = registers.update_condition_code(value);
-                ExecutionResult::Continue
-            }
-            None => ExecutionResult::Error("Memory read out of bounds".to_string()),
+            Some(value) => Self::write_result(registers, dr, value),
+            None => Ok(ExecutionResult::Error(
+                "Memory read out of bounds".to_string(),
+            )),
         }
     }
 
     fn execute_str(
-        instruction: u16,
+        sr: Registers,
+        base: Registers,
+        offset6: i16,
         memory: &mut Memory,
         registers: &mut RegisterFile,
-    ) -> ExecutionResult {
-        let sr = extract_dr(instruction);
-        let base_reg = extract_sr1(instruction);
-        let offset6 = extract_offset6(instruction);
-
-        let base_value = registers.read(Registers::from(base_reg)).unwrap_or(0);
-        let offset = sign_extend_offset6(offset6);
-        let address = base_value + offset;
-        let value = registers.read(Registers::from(sr)).unwrap_or(0);
+    ) -> Result<ExecutionResult, LC3Error> {
+        let base_value = Self::read_operand(registers, base)?;
+        let address = base_value.wrapping_add(offset6 as u16);
+        let value = Self::read_operand(registers, sr)?;
 
-        match memory.write(address, value) {
-            Ok(_) => ExecutionResult::Continue,
-            Err(_) => ExecutionResult::Error("Memory write out of bounds".to_string()),
-        }
+        Ok(Self::store(memory, address, value))
     }
 
     //=== Perform bitwise NOT operation ===
-    fn execute_not(instruction: u16, registers: &mut RegisterFile) -> ExecutionResult {
-        let dr = extract_dr(instruction);
-        let sr = extract_sr1(instruction);
-
-        let sr_value = registers.read(Registers::from(sr)).unwrap_or(0);
-        let result = !sr_value;
-
-        let _ = registers.write(Registers::from(dr), result);
-        let _ = registers.update_condition_code(result);
+    fn execute_not(
+        dr: Registers,
+        sr: Registers,
+        registers: &mut RegisterFile,
+    ) -> Result<ExecutionResult, LC3Error> {
+        let result = !Self::read_operand(registers, sr)?;
 
-        ExecutionResult::Continue
+        Self::write_result(registers, dr, result)
     }
 
     fn execute_ldi(
-        instruction: u16,
+        dr: Registers,
+        offset: i16,
         memory: &Memory,
         registers: &mut RegisterFile,
-    ) -> ExecutionResult {
-        let dr = extract_dr(instruction);
-        let pc_offset9 = extract_pc_offset9(instruction);
-
-        let pc = registers.get_pc();
-        let indirect_address = pc + sign_extend_pc_offset9(pc_offset9);
+    ) -> Result<ExecutionResult, LC3Error> {
+        let indirect_address = Self::pc_relative(offset, registers);
 
         match memory.read(indirect_address) {
             Some(direct_address) => match memory.read(direct_address) {
-                Some(value) => {
-                    let _ = registers.write(Registers::from(dr), value);
-                    let _ = registers.update_condition_code(value);
-                    ExecutionResult::Continue
-                }
-                None => ExecutionResult::Error("Indirect memory read out of bounds".to_string()),
+                Some(value) => Self::write_result(registers, dr, value),
+                None => Ok(ExecutionResult::Error(
+                    "Indirect memory read out of bounds".to_string(),
+                )),
             },
-            None => ExecutionResult::Error("Memory read out of bounds".to_string()),
+            None => Ok(ExecutionResult::Error(
+                "Memory read out of bounds".to_string(),
+            )),
         }
     }
 
     fn execute_sti(
-        instruction: u16,
+        sr: Registers,
+        offset: i16,
         memory: &mut Memory,
         registers: &mut RegisterFile,
-    ) -> ExecutionResult {
-        let sr = extract_dr(instruction);
-        let pc_offset9 = extract_pc_offset9(instruction);
+    ) -> Result<ExecutionResult, LC3Error> {
+        let indirect_address = Self::pc_relative(offset, registers);
+        let value = Self::read_operand(registers, sr)?;
 
-        let pc = registers.get_pc();
-        let indirect_address = pc + sign_extend_pc_offset9(pc_offset9);
-        let value = registers.read(Registers::from(sr)).unwrap_or(0);
-
-        match memory.read(indirect_address) {
+        let result = match memory.read(indirect_address) {
+            Some(direct_address) if memory.is_readonly(direct_address) => {
+                ExecutionResult::Error("write to read-only memory".to_string())
+            }
             Some(direct_address) => match memory.write(direct_address, value) {
                 Ok(_) => ExecutionResult::Continue,
                 Err(_) => ExecutionResult::Error("Indirect memory write out of bounds".to_string()),
             },
             None => ExecutionResult::Error("Memory read out of bounds".to_string()),
+        };
+        Ok(result)
+    }
+
+    fn execute_jmp(
+        base: Registers,
+        registers: &mut RegisterFile,
+    ) -> Result<ExecutionResult, LC3Error> {
+        let base_value = Self::read_operand(registers, base)?;
+        registers.set_pc(base_value)?;
+
+        Ok(ExecutionResult::Continue)
+    }
+
+    fn execute_lea(
+        dr: Registers,
+        offset: i16,
+        registers: &mut RegisterFile,
+    ) -> Result<ExecutionResult, LC3Error> {
+        let address = Self::pc_relative(offset, registers);
+
+        Self::write_result(registers, dr, address)
+    }
+
+    //=== Extended GETS trap: read one line of input into memory ===
+    pub fn execute_gets(
+        memory: &mut Memory,
+        registers: &mut RegisterFile,
+        io: &mut dyn Io,
+    ) -> ExecutionResult {
+        let buffer = registers.read(Registers::R0).unwrap_or(0);
+        let max_len = registers.read(Registers::R1).unwrap_or(0) as usize;
+
+        let mut line = Vec::new();
+        while let Some(byte) = io.read_byte() {
+            if byte == b'\n' {
+                break;
+            }
+            line.push(byte);
+        }
+        if line.last() == Some(&b'\r') {
+            line.pop();
         }
+        line.truncate(max_len);
+
+        let terminated = line.iter().map(|&b| b as u16).chain(std::iter::once(0));
+        for (i, word) in terminated.enumerate() {
+            let address = buffer.wrapping_add(i as u16);
+            if memory.is_readonly(address) {
+                return ExecutionResult::Error("write to read-only memory".to_string());
+            }
+            let _ = memory.write(address, word);
+        }
+
+        let _ = registers.write(Registers::R0, line.len() as u16);
+        ExecutionResult::Continue
     }
 
-    fn execute_jmp(instruction: u16, registers: &mut RegisterFile) -> ExecutionResult {
-        let base_reg = extract_sr1(instruction);
-        let base_value = registers.read(Registers::from(base_reg)).unwrap_or(0);
-        let _ = registers.set_pc(base_value);
+    //=== Extended PUTHEX trap: print R0 as four hex digits ===
+    pub fn execute_puthex(registers: &RegisterFile, io: &mut dyn Io) -> ExecutionResult {
+        let value = registers.read(Registers::R0).unwrap_or(0);
 
+        format!("{:04X}", value)
+            .bytes()
+            .for_each(|byte| io.write_byte(byte));
+        io.flush();
         ExecutionResult::Continue
     }
 
-    fn execute_lea(instruction: u16, registers: &mut RegisterFile) -> ExecutionResult {
-        let dr = extract_dr(instruction);
-        let pc_offset9 = extract_pc_offset9(instruction);
+    //=== GETC: read one character into R0 without echo ===
+    // EOF reads as 0; COND is left alone, as GETC does not set NZP
+    pub fn execute_getc(registers: &mut RegisterFile, io: &mut dyn Io) -> ExecutionResult {
+        let value = io.read_byte().unwrap_or(0) as u16;
 
-        let pc = registers.get_pc();
-        let address = pc + sign_extend_pc_offset9(pc_offset9);
+        let _ = registers.write(Registers::R0, value);
+        ExecutionResult::Continue
+    }
 
-        let _ = registers.write(Registers::from(dr), address);
-        let _ = registers.update_condition_code(address);
+    //=== IN: read one character into R0 and echo it ===
+    // EOF reads as 0 and echoes nothing; like GETC, COND is left alone
+    pub fn execute_in(registers: &mut RegisterFile, io: &mut dyn Io) -> ExecutionResult {
+        let byte = io.read_byte();
+        if let Some(byte) = byte {
+            io.write_byte(byte);
+            io.flush();
+        }
 
+        let _ = registers.write(Registers::R0, byte.unwrap_or(0) as u16);
         ExecutionResult::Continue
     }
 
-    fn execute_trap(
-        instruction: u16,
-        _memory: &mut Memory,
-        _registers: &mut RegisterFile,
+    //=== OUT: write the low byte of R0 ===
+    pub fn execute_out(registers: &RegisterFile, io: &mut dyn Io) -> ExecutionResult {
+        let byte = (registers.read(Registers::R0).unwrap_or(0) & 0xFF) as u8;
+
+        io.write_byte(byte);
+        io.flush();
+        ExecutionResult::Continue
+    }
+
+    //=== PUTS: write the null-terminated string at R0, one character per word ===
+    // Strings longer than `max_len` are treated as unterminated
+    pub fn execute_puts(
+        memory: &Memory,
+        registers: &RegisterFile,
+        io: &mut dyn Io,
+        max_len: usize,
     ) -> ExecutionResult {
-        let trap_vector = extract_trap_vector(instruction);
+        let mut address = Some(registers.read(Registers::R0).unwrap_or(0));
+        let mut bytes = Vec::new();
 
-        match TrapVectors::from_u16(trap_vector) {
-            Some(TrapVectors::GETC) => {
-                println!("TRAP: GETC (not implemented)");
-                ExecutionResult::Continue
+        loop {
+            match address.and_then(|a| memory.read(a)) {
+                Some(0) => break,
+                Some(_) if bytes.len() == max_len => {
+                    return ExecutionResult::Error(format!(
+                        "PUTS string exceeds maximum length of {}",
+                        max_len
+                    ));
+                }
+                Some(word) => bytes.push((word & 0xFF) as u8),
+                None => return ExecutionResult::Error("PUTS read out of bounds".to_string()),
             }
-            Some(TrapVectors::OUT) => {
-                println!("TRAP: OUT (not implemented)");
-                ExecutionResult::Continue
+            address = address.and_then(|a| a.checked_add(1));
+        }
+
+        bytes.into_iter().for_each(|byte| io.write_byte(byte));
+        io.flush();
+        ExecutionResult::Continue
+    }
+
+    //=== PUTSP: write the string at R0 packed two characters per word, low byte first ===
+    // A zero high byte ends the string early; the limit counts characters as for PUTS
+    pub fn execute_putsp(
+        memory: &Memory,
+        registers: &RegisterFile,
+        io: &mut dyn Io,
+        max_len: usize,
+    ) -> ExecutionResult {
+        let mut address = Some(registers.read(Registers::R0).unwrap_or(0));
+        let mut bytes = Vec::new();
+
+        'words: loop {
+            let Some(word) = address.and_then(|a| memory.read(a)) else {
+                return ExecutionResult::Error("PUTSP read out of bounds".to_string());
+            };
+            for byte in [(word & 0xFF) as u8, (word >> 8) as u8] {
+                if byte == 0 {
+                    break 'words;
+                }
+                if bytes.len() == max_len {
+                    return ExecutionResult::Error(format!(
+                        "PUTSP string exceeds maximum length of {}",
+                        max_len
+                    ));
+                }
+                bytes.push(byte);
             }
+            address = address.and_then(|a| a.checked_add(1));
+        }
+
+        bytes.into_iter().for_each(|byte| io.write_byte(byte));
+        io.flush();
+        ExecutionResult::Continue
+    }
+
+    fn execute_trap(
+        vector: u8,
+        memory: &mut Memory,
+        registers: &mut RegisterFile,
+        io: &mut dyn Io,
+    ) -> ExecutionResult {
+        let trap_vector = vector as u16;
+
+        match TrapVectors::from_u16(trap_vector) {
+            Some(TrapVectors::GETC) => Self::execute_getc(registers, io),
+            Some(TrapVectors::OUT) => Self::execute_out(registers, io),
             Some(TrapVectors::PUTS) => {
-                println!("TRAP: PUTS (not implemented)");
-                ExecutionResult::Continue
-            }
-            Some(TrapVectors::IN) => {
-                println!("TRAP: IN (not implemented)");
-                ExecutionResult::Continue
+                Self::execute_puts(memory, registers, io, DEFAULT_MAX_STRING_LENGTH)
             }
+            Some(TrapVectors::IN) => Self::execute_in(registers, io),
             Some(TrapVectors::PUTSP) => {
-                println!("TRAP: PUTSP (not implemented)");
-                ExecutionResult::Continue
-            }
-            Some(TrapVectors::HALT) => {
-                println!("TRAP: HALT");
-                ExecutionResult::Halt
+                Self::execute_putsp(memory, registers, io, DEFAULT_MAX_STRING_LENGTH)
             }
+            Some(TrapVectors::HALT) => ExecutionResult::Halt,
             None => ExecutionResult::Error(format!("Unknown trap vector: 0x{:02X}", trap_vector)),
         }
     }
 }
 
 // From<u16> for Registers is now implemented in types.rs
+
+/// Check whether executing an instruction can update the condition codes
+/// ADD, AND, NOT, LD, LDI, LDR and LEA set COND; stores, branches, jumps and traps leave it alone
+/// LEA is included because `execute_lea` updates COND (original LC-3 semantics)
+pub fn sets_condition_codes(instruction: u16) -> bool {
+    matches!(
+        Opcodes::from_u16(extract_opcode(instruction)),
+        Some(Opcodes::ADD)
+            | Some(Opcodes::AND)
+            | Some(Opcodes::NOT)
+            | Some(Opcodes::LD)
+            | Some(Opcodes::LDI)
+            | Some(Opcodes::LDR)
+            | Some(Opcodes::LEA)
+    )
+}
+
+/// Check whether an instruction writes to memory (ST, STR, STI)
+pub fn is_store(instruction: u16) -> bool {
+    matches!(
+        Opcodes::from_u16(extract_opcode(instruction)),
+        Some(Opcodes::ST) | Some(Opcodes::STR) | Some(Opcodes::STI)
+    )
+}
+
+/// Compute the data address a load/store will access, or None for other instructions
+/// `pc` is the already-incremented PC; LDI/STI resolve through the pointer in memory
+pub fn effective_address(
+    instruction: u16,
+    pc: u16,
+    registers: &RegisterFile,
+    memory: &Memory,
+) -> Option<u16> {
+    let pc_relative = || pc.wrapping_add(sign_extend_pc_offset9(extract_pc_offset9(instruction)));
+    let base_relative = || {
+        let base = registers.read(Registers::try_from_field(extract_sr1(instruction)).ok()?)?;
+        Some(base.wrapping_add(sign_extend_offset6(extract_offset6(instruction))))
+    };
+
+    match Opcodes::from_u16(extract_opcode(instruction))? {
+        Opcodes::LD | Opcodes::ST => Some(pc_relative()),
+        Opcodes::LDR | Opcodes::STR => base_relative(),
+        Opcodes::LDI | Opcodes::STI => memory.read(pc_relative()),
+        _ => None,
+    }
+}
+
+/// List the contents of an LC-3 `.obj` image (any format `parse_object` accepts)
+/// One line per word: address, raw word and its disassembly
+pub fn disassemble_obj(bytes: &[u8]) -> Result<String, LC3Error> {
+    let (origin, words) = parse_object(bytes)?;
+
+    let lines: Vec<String> = words
+        .into_iter()
+        .enumerate()
+        .map(|(i, word)| {
+            let address = origin.wrapping_add(i as u16);
+            format!(
+                "x{:04X}  x{:04X}  {}",
+                address,
+                word,
+                disassemble(word, address)
+            )
+        })
+        .collect();
+
+    Ok(lines.join("\n"))
+}
+
+/// Target of a PC-relative BR or JSR located at `address`, None for anything else
+/// (JSRR, JMP and RET jump through a register and can't be resolved statically)
+pub fn branch_target(instruction: u16, address: u16) -> Option<u16> {
+    let next = address.wrapping_add(1);
+
+    match Opcodes::from_u16(extract_opcode(instruction))? {
+        Opcodes::BR => {
+            Some(next.wrapping_add(sign_extend_pc_offset9(extract_pc_offset9(instruction))))
+        }
+        Opcodes::JSR if instruction & 0x800 != 0 => {
+            Some(next.wrapping_add(sign_extend_pc_offset11(extract_pc_offset11(instruction))))
+        }
+        _ => None,
+    }
+}
+
+/// Build a basic-block control-flow graph of `words` loaded at `origin` as Graphviz DOT.
+/// Blocks split at branches and branch targets; register-indirect jumps lead to an
+/// "unknown" node and HALT ends a block with no successors.
+pub fn cfg_dot(words: &[u16], origin: u16) -> String {
+    let end = origin as usize + words.len();
+    let in_program = |address: u16| (origin as usize..end).contains(&(address as usize));
+    let word_at = |address: u16| words[address.wrapping_sub(origin) as usize];
+
+    // Successor addresses of the instruction at `address`, plus whether it can also
+    // jump somewhere only known at run time
+    let successors = |address: u16| -> (Vec<u16>, bool) {
+        let instruction = word_at(address);
+        let next = address.wrapping_add(1);
+        let target = branch_target(instruction, address);
+
+        match Opcodes::from_u16(extract_opcode(instruction)) {
+            Some(Opcodes::BR) => match (instruction >> 9) & 0x7 {
+                0 => (vec![next], false),
+                0x7 => (target.into_iter().collect(), false),
+                _ => (target.into_iter().chain([next]).collect(), false),
+            },
+            Some(Opcodes::JSR) => (target.into_iter().chain([next]).collect(), target.is_none()),
+            Some(Opcodes::JMP) | Some(Opcodes::RTI) => (Vec::new(), true),
+            Some(Opcodes::TRAP)
+                if extract_trap_vector(instruction) == TrapVectors::HALT.to_u16() =>
+            {
+                (Vec::new(), false)
+            }
+            _ => (vec![next], false),
+        }
+    };
+    let ends_block = |address: u16| {
+        let (targets, unknown) = successors(address);
+        unknown || targets != [address.wrapping_add(1)]
+    };
+
+    let mut leaders = std::collections::BTreeSet::new();
+    if !words.is_empty() {
+        leaders.insert(origin);
+    }
+    for i in 0..words.len() {
+        let address = origin.wrapping_add(i as u16);
+        if ends_block(address) {
+            let (targets, _) = successors(address);
+            leaders.extend(targets.into_iter().filter(|&target| in_program(target)));
+            if in_program(address.wrapping_add(1)) {
+                leaders.insert(address.wrapping_add(1));
+            }
+        }
+    }
+
+    let mut dot = String::from("digraph cfg {\n    node [shape=box];\n");
+    let mut uses_unknown = false;
+    let leaders: Vec<u16> = leaders.into_iter().collect();
+    for (i, &start) in leaders.iter().enumerate() {
+        let last = leaders
+            .get(i + 1)
+            .map(|next| next.wrapping_sub(1))
+            .unwrap_or((end - 1) as u16);
+        dot.push_str(&format!(
+            "    \"x{:04X}\" [label=\"x{:04X}-x{:04X}\"];\n",
+            start, start, last
+        ));
+
+        let (targets, unknown) = successors(last);
+        for target in targets {
+            let name = if in_program(target) {
+                format!("x{:04X}", target)
+            } else {
+                "unknown".to_string()
+            };
+            uses_unknown |= name == "unknown";
+            dot.push_str(&format!("    \"x{:04X}\" -> \"{}\";\n", start, name));
+        }
+        if unknown {
+            uses_unknown = true;
+            dot.push_str(&format!("    \"x{:04X}\" -> \"unknown\";\n", start));
+        }
+    }
+    if uses_unknown {
+        dot.push_str("    \"unknown\" [shape=ellipse];\n");
+    }
+    dot.push_str("}\n");
+    dot
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::io::BufferIo;
+
+    fn run_trap(
+        vector: TrapVectors,
+        memory: &mut Memory,
+        registers: &mut RegisterFile,
+        input: &[u8],
+    ) -> (ExecutionResult, Vec<u8>) {
+        let mut io = BufferIo::new(input);
+        let output = io.output_handle();
+        let result = InstructionExecutor::execute_instruction(
+            0xF000 | vector.to_u16(),
+            memory,
+            registers,
+            &mut io,
+        );
+        let output = output.borrow().clone();
+        (result, output)
+    }
+
+    #[test]
+    fn add_sets_condition_codes() {
+        // ADD R1, R2, #2
+        assert!(sets_condition_codes(0x12A2));
+    }
+
+    #[test]
+    fn st_leaves_condition_codes_alone() {
+        // ST R0, #1
+        assert!(!sets_condition_codes(0x3001));
+    }
+
+    #[test]
+    fn decoded_and_raw_paths_agree() {
+        let mut io = BufferIo::new(b"");
+        let mut memory = Memory::new();
+        let mut raw = RegisterFile::new();
+        let mut decoded = RegisterFile::new();
+        raw.write(Registers::R2, 40).unwrap();
+        decoded.write(Registers::R2, 40).unwrap();
+
+        // ADD R1, R2, #2
+        InstructionExecutor::execute_instruction(0x12A2, &mut memory, &mut raw, &mut io);
+        let add = Instruction::Add {
+            dr: Registers::R1,
+            sr1: Registers::R2,
+            src2: AddSource::Immediate(2),
+        };
+        InstructionExecutor::execute_decoded(add, &mut memory, &mut decoded, &mut io);
+
+        assert_eq!(raw.read(Registers::R1), Some(42));
+        assert_eq!(raw.snapshot(), decoded.snapshot());
+    }
+
+    #[test]
+    fn jsr_negative_offset_saves_the_incremented_pc() {
+        let mut io = BufferIo::new(b"");
+        let mut memory = Memory::new();
+        let mut registers = RegisterFile::new();
+        // Fetched from x3004, so PC has already moved on to x3005
+        registers.set_pc(0x3005).unwrap();
+
+        // JSR #-4
+        let result =
+            InstructionExecutor::execute_instruction(0x4FFC, &mut memory, &mut registers, &mut io);
+
+        assert_eq!(result, ExecutionResult::Continue);
+        assert_eq!(registers.get_pc(), 0x3001);
+        assert_eq!(registers.read(Registers::R7), Some(0x3005));
+    }
+
+    #[test]
+    fn jsrr_r7_jumps_to_the_old_r7() {
+        let mut io = BufferIo::new(b"");
+        let mut memory = Memory::new();
+        let mut registers = RegisterFile::new();
+        registers.set_pc(0x3001).unwrap();
+        registers.write(Registers::R7, 0x4000).unwrap();
+
+        // JSRR R7
+        InstructionExecutor::execute_instruction(0x41C0, &mut memory, &mut registers, &mut io);
+
+        assert_eq!(registers.get_pc(), 0x4000);
+        assert_eq!(registers.read(Registers::R7), Some(0x3001));
+    }
+
+    #[test]
+    fn non_operand_registers_are_errors() {
+        let mut io = BufferIo::new(b"");
+        let mut memory = Memory::new();
+        let mut registers = RegisterFile::new();
+        let add = Instruction::Add {
+            dr: Registers::PC,
+            sr1: Registers::R0,
+            src2: AddSource::Immediate(1),
+        };
+
+        let result =
+            InstructionExecutor::execute_decoded(add, &mut memory, &mut registers, &mut io);
+
+        assert_eq!(
+            result,
+            ExecutionResult::Error(LC3Error::InvalidRegister(Registers::PC as u16).to_string())
+        );
+        assert_eq!(registers.get_pc(), 0);
+    }
+
+    #[test]
+    fn in_reads_and_echoes_through_io() {
+        let mut memory = Memory::new();
+        let mut registers = RegisterFile::new();
+
+        let (result, output) = run_trap(TrapVectors::IN, &mut memory, &mut registers, b"k");
+
+        assert_eq!(result, ExecutionResult::Continue);
+        assert_eq!(registers.read(Registers::R0), Some(b'k' as u16));
+        assert_eq!(output, b"k");
+    }
+
+    #[test]
+    fn putsp_writes_packed_characters_through_io() {
+        let mut memory = Memory::new();
+        let mut registers = RegisterFile::new();
+        memory
+            .load_program(0x4000, &[0x6548, 0x6C6C, 0x006F, 0x0000])
+            .unwrap();
+        registers.write(Registers::R0, 0x4000).unwrap();
+
+        let (result, output) = run_trap(TrapVectors::PUTSP, &mut memory, &mut registers, b"");
+
+        assert_eq!(result, ExecutionResult::Continue);
+        assert_eq!(output, b"Hello");
+    }
+
+    #[test]
+    fn halt_prints_nothing() {
+        let mut memory = Memory::new();
+        let mut registers = RegisterFile::new();
+
+        let (result, output) = run_trap(TrapVectors::HALT, &mut memory, &mut registers, b"");
+
+        assert_eq!(result, ExecutionResult::Halt);
+        assert!(output.is_empty());
+    }
+
+    #[test]
+    fn getc_and_out_use_injected_io() {
+        let mut memory = Memory::new();
+        let mut registers = RegisterFile::new();
+
+        let (_, output) = run_trap(TrapVectors::GETC, &mut memory, &mut registers, b"z");
+        assert_eq!(registers.read(Registers::R0), Some(b'z' as u16));
+        assert!(output.is_empty());
+
+        let (_, output) = run_trap(TrapVectors::OUT, &mut memory, &mut registers, b"");
+        assert_eq!(output, b"z");
+    }
+
+    #[test]
+    fn gets_stores_a_line_and_its_length() {
+        let mut io = BufferIo::new(b"hello\nworld");
+        let mut memory = Memory::new();
+        let mut registers = RegisterFile::new();
+        registers.write(Registers::R0, 0x4000).unwrap();
+        registers.write(Registers::R1, 20).unwrap();
+
+        let result = InstructionExecutor::execute_gets(&mut memory, &mut registers, &mut io);
+
+        assert_eq!(result, ExecutionResult::Continue);
+        assert_eq!(registers.read(Registers::R0), Some(5));
+        let stored: Vec<u16> = (0x4000..0x4006).map(|a| memory.read(a).unwrap()).collect();
+        assert_eq!(stored, [b'h', b'e', b'l', b'l', b'o', 0].map(u16::from));
+    }
+
+    #[test]
+    fn puthex_prints_r0_as_four_hex_digits() {
+        let mut io = BufferIo::new(b"");
+        let output = io.output_handle();
+        let mut registers = RegisterFile::new();
+        registers.write(Registers::R0, 0x3F0A).unwrap();
+
+        let result = InstructionExecutor::execute_puthex(&registers, &mut io);
+
+        assert_eq!(result, ExecutionResult::Continue);
+        assert_eq!(*output.borrow(), b"3F0A");
+    }
+
+    /// Run a JSR word with PC already incremented to `pc`; returns the new PC and R7
+    fn run_jsr(pc: u16, instruction: u16) -> (u16, u16) {
+        let mut io = BufferIo::new(b"");
+        let mut memory = Memory::new();
+        let mut registers = RegisterFile::new();
+        registers.set_pc(pc).unwrap();
+
+        let result = InstructionExecutor::execute_instruction(
+            instruction,
+            &mut memory,
+            &mut registers,
+            &mut io,
+        );
+
+        assert_eq!(result, ExecutionResult::Continue);
+        (registers.get_pc(), registers.read(Registers::R7).unwrap())
+    }
+
+    #[test]
+    fn jsr_offset_boundaries() {
+        // (incremented PC, JSR word, expected target)
+        let cases = [
+            (0x3001, 0x4BFF, 0x3400), // +1023, the largest positive offset
+            (0x3001, 0x4C00, 0x2C01), // -1024, the most negative offset
+            (0x3001, 0x4801, 0x3002), // +1
+            (0x3001, 0x4FFF, 0x3000), // -1
+            (0xFFFF, 0x4BFF, 0x03FE), // wraps past xFFFF
+        ];
+
+        for (pc, instruction, target) in cases {
+            assert_eq!(
+                run_jsr(pc, instruction),
+                (target, pc),
+                "JSR 0x{:04X} at PC 0x{:04X}",
+                instruction,
+                pc
+            );
+        }
+    }
+
+    #[test]
+    fn disassemble_obj_lists_each_word() {
+        let bytes = [0x30, 0x00, 0x10, 0x21, 0xF0, 0x25];
+
+        let listing = disassemble_obj(&bytes).unwrap();
+
+        assert_eq!(
+            listing,
+            format!(
+                "x3000  x1021  {}\nx3001  xF025  {}",
+                disassemble(0x1021, 0x3000),
+                disassemble(0xF025, 0x3001)
+            )
+        );
+        assert!(listing.contains("ADD"));
+        assert!(disassemble_obj(&[0x30]).is_err());
+    }
+
+    #[test]
+    fn jsr_backward_from_the_bottom_of_memory_wraps() {
+        // -1024 from incremented PC x0001 lands near the top of memory
+        assert_eq!(run_jsr(0x0001, 0x4C00), (0xFC01, 0x0001));
+    }
+
+    #[test]
+    fn cfg_dot_splits_at_a_branch() {
+        // ADD R0, R0, #-1; BRz x3003; ADD R0, R0, #1; HALT
+        let dot = cfg_dot(&[0x103F, 0x0401, 0x1021, 0xF025], 0x3000);
+
+        assert_eq!(
+            dot,
+            "digraph cfg {\n    node [shape=box];\n    \
+             \"x3000\" [label=\"x3000-x3001\"];\n    \
+             \"x3000\" -> \"x3003\";\n    \
+             \"x3000\" -> \"x3002\";\n    \
+             \"x3002\" [label=\"x3002-x3002\"];\n    \
+             \"x3002\" -> \"x3003\";\n    \
+             \"x3003\" [label=\"x3003-x3003\"];\n}\n"
+        );
+        assert!(!dot.contains("unknown"));
+    }
+
+    fn run_at(pc: u16, instruction: u16, memory: &mut Memory, registers: &mut RegisterFile) {
+        let mut io = BufferIo::new(b"");
+        registers.set_pc(pc).unwrap();
+
+        let result =
+            InstructionExecutor::execute_instruction(instruction, memory, registers, &mut io);
+
+        assert_eq!(result, ExecutionResult::Continue);
+    }
+
+    #[test]
+    fn loads_and_stores_wrap_below_x0000() {
+        let mut memory = Memory::new();
+        let mut registers = RegisterFile::new();
+        memory.write(0xFFF1, 0x1234).unwrap();
+        memory.write(0xFFFE, 0x5678).unwrap();
+
+        // LD R0, #-16 from incremented PC x0001
+        run_at(0x0001, 0x21F0, &mut memory, &mut registers);
+        assert_eq!(registers.read(Registers::R0), Some(0x1234));
+
+        // LDR R2, R1, #-4 with R1 = x0002
+        registers.write(Registers::R1, 0x0002).unwrap();
+        run_at(0x0001, 0x647C, &mut memory, &mut registers);
+        assert_eq!(registers.read(Registers::R2), Some(0x5678));
+
+        // ST R2, #-16 from incremented PC x0002
+        run_at(0x0002, 0x35F0, &mut memory, &mut registers);
+        assert_eq!(memory.read(0xFFF2), Some(0x5678));
+
+        // STR R0, R1, #-3 with R1 = x0002
+        run_at(0x0001, 0x707D, &mut memory, &mut registers);
+        assert_eq!(memory.read(0xFFFF), Some(0x1234));
+    }
+
+    #[test]
+    fn br_backward_from_low_memory_wraps() {
+        let mut memory = Memory::new();
+        let mut registers = RegisterFile::new();
+        registers.write(Registers::COND, Flags::ZRO as u16).unwrap();
+
+        // BRz #-4 from incremented PC x0002
+        run_at(0x0002, 0x05FC, &mut memory, &mut registers);
+
+        assert_eq!(registers.get_pc(), 0xFFFE);
+    }
+}
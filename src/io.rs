@@ -0,0 +1,69 @@
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::io::{Read, Write};
+use std::rc::Rc;
+
+/// Byte-level console used by the trap handlers
+pub trait Io {
+    /// Next input byte, or None at end of input
+    fn read_byte(&mut self) -> Option<u8>;
+
+    fn write_byte(&mut self, byte: u8);
+
+    /// Called once a trap has finished writing, so interactive output shows up promptly
+    fn flush(&mut self) {}
+}
+
+/// The real console: stdin and stdout
+#[derive(Debug, Default, Clone, Copy)]
+pub struct StdIo;
+
+impl Io for StdIo {
+    fn read_byte(&mut self) -> Option<u8> {
+        let mut byte = [0u8; 1];
+        match std::io::stdin().read(&mut byte) {
+            Ok(1) => Some(byte[0]),
+            _ => None,
+        }
+    }
+
+    fn write_byte(&mut self, byte: u8) {
+        let _ = std::io::stdout().write_all(&[byte]);
+    }
+
+    fn flush(&mut self) {
+        let _ = std::io::stdout().flush();
+    }
+}
+
+/// In-memory console for tests and headless runs.
+/// Output goes to a shared buffer so it can be read after the VM takes ownership.
+#[derive(Debug, Default, Clone)]
+pub struct BufferIo {
+    input: VecDeque<u8>,
+    output: Rc<RefCell<Vec<u8>>>,
+}
+
+impl BufferIo {
+    pub fn new(input: &[u8]) -> Self {
+        Self {
+            input: input.iter().copied().collect(),
+            output: Rc::default(),
+        }
+    }
+
+    /// Handle onto everything written so far
+    pub fn output_handle(&self) -> Rc<RefCell<Vec<u8>>> {
+        Rc::clone(&self.output)
+    }
+}
+
+impl Io for BufferIo {
+    fn read_byte(&mut self) -> Option<u8> {
+        self.input.pop_front()
+    }
+
+    fn write_byte(&mut self, byte: u8) {
+        self.output.borrow_mut().push(byte);
+    }
+}
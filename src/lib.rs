@@ -1,21 +1,47 @@
 pub mod types;
+pub mod io;
+pub mod devices;
 pub mod registers;
 pub mod memory;
 pub mod opcodes;
 pub mod instructions;
+pub mod assembler;
+pub mod disassembler;
 pub mod vm;
+pub mod programs;
+pub mod trace;
+pub mod trap_helpers;
+pub mod warnings;
+#[cfg(feature = "testing")]
+pub mod testing;
 
 pub use types::{
-    Registers, Flags, Opcodes, TrapVectors, LC3Error,
-    MEMORY_MAX, REG_COUNT, PC_START,
+    Registers, Flags, Opcodes, TrapVectors, LC3Error, Instruction, AddSource, decode,
+    MEMORY_MAX, REG_COUNT, PC_START, DEVICE_SPACE_START, KBSR, KBDR,
+    KBSR_READY, KBSR_INTERRUPT_ENABLE, KEYBOARD_INTERRUPT, KEYBOARD_PRIORITY, TIMER_PRIORITY, DSR, DDR, DSR_READY, DEFAULT_MAX_STRING_LENGTH, TRAP_GETS, TRAP_PUTHEX,
+    PSR_USER_MODE, PSR_PRIORITY_MASK, INTERRUPT_VECTOR_TABLE, PRIVILEGE_MODE_EXCEPTION,
+    ACCESS_CONTROL_VIOLATION, DEFAULT_SUPERVISOR_STACK,
     extract_opcode, extract_dr, extract_sr1, extract_sr2,
     extract_imm5_flag, extract_imm5, extract_pc_offset9, extract_pc_offset11,
-    extract_offset6, extract_trap_vector,
+    extract_offset6, extract_trap_vector, field,
     sign_extend_imm5, sign_extend_offset6, sign_extend_pc_offset9,
     sign_extend_pc_offset11, sign_extend,
 };
 
+pub use io::{BufferIo, Io, StdIo};
+pub use devices::{Display, Keyboard};
 pub use registers::RegisterFile;
-pub use memory::Memory;
-pub use instructions::{InstructionExecutor, ExecutionResult};
-pub use vm::LC3VM;
+pub use memory::{AddrMode, Memory, MemoryView, parse_object};
+pub use instructions::{
+    InstructionExecutor, ExecutionResult, ResPolicy, branch_target, cfg_dot, disassemble_obj, effective_address, is_store,
+    sets_condition_codes,
+};
+pub use assembler::{AssembledProgram, assemble, assemble_program, assemble_with_defines};
+pub use disassembler::disassemble;
+pub use trace::{Divergence, TraceRecord, first_divergence};
+pub use warnings::{Warning, WarningHandler};
+pub use vm::{
+    AluOp, BREAKPOINT_STEP_LIMIT, BreakReason, CycleModel, DEFAULT_ENERGY_COSTS, Effects,
+    ExecutionReport, HaltStatus, LC3VM, ProgramLayout, RunOutcome, RunStats, SafeRunOutcome,
+    StepHook, StepInfo, TrapObserver, UnknownTrapHandler, VmDiff, VmSnapshot, ZERO_RUN_LIMIT,
+};
@@ -1,8 +1,14 @@
 use lc3::instructions::InstructionExecutor;
 use lc3::types::{Opcodes, PC_START, extract_opcode};
 use lc3::*;
+use std::path::Path;
 
 fn main() {
+    if let Some(path) = std::env::args().nth(1) {
+        run_object_file(Path::new(&path));
+        return;
+    }
+
     let mut vm = LC3VM::new();
 
     let first_program = vec![0x3000];
@@ -97,3 +103,16 @@ fn main() {
         );
     }
 }
+
+fn run_object_file(path: &Path) {
+    let mut vm = LC3VM::new();
+
+    if let Err(e) = vm.load_object_from_path(path) {
+        eprintln!("Failed to load {}: {}", path.display(), e);
+        std::process::exit(1);
+    }
+    if let Err(e) = vm.run() {
+        eprintln!("Error executing program: {}", e);
+        std::process::exit(1);
+    }
+}
@@ -1,24 +1,73 @@
+use std::cell::RefCell;
+
+use crate::devices::{Display, Keyboard};
 use crate::registers::RegisterFile;
-use crate::types::{MEMORY_MAX, LC3Error};
+use crate::types::{MEMORY_MAX, LC3Error, DDR, DSR, DSR_READY, KBDR, KBSR};
+
 
+/// How `Memory::hexdump` labels and groups memory
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AddrMode {
+    /// One 16-bit word per address (native LC-3 view)
+    #[default]
+    Word,
+    /// Each word shown as two big-endian bytes with byte addresses (word address * 2)
+    Byte,
+}
 
 #[derive(Debug)]
 pub struct Memory {
    
-    locations: [u16; MEMORY_MAX],
+    locations: Box<[u16]>,
+
+    size: usize,
+
+    loaded: Vec<bool>,
+
+    readonly: Vec<(u16, u16)>,
+
+    keyboard: Option<RefCell<Keyboard>>,
+
+    display: Option<Display>,
 }
 
 impl Memory {
     
     pub fn new() -> Self {
+        Self::with_size(MEMORY_MAX)
+    }
+
+    /// Memory limited to the first `words` addresses (capped at 64K);
+    /// accesses at or above the limit fail like out-of-range addresses
+    pub fn with_size(words: usize) -> Self {
         Self {
-            locations: [0u16; MEMORY_MAX],
+            locations: vec![0u16; MEMORY_MAX].into_boxed_slice(),
+            size: words.min(MEMORY_MAX),
+            loaded: vec![false; MEMORY_MAX],
+            readonly: Vec::new(),
+            keyboard: None,
+            display: None,
         }
     }
 
+    /// Number of addressable words
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
    
     pub fn read(&self, address: u16) -> Option<u16> {
-        if address as usize >= MEMORY_MAX {
+        if let Some(keyboard) = &self.keyboard {
+            match address {
+                KBSR => return Some(keyboard.borrow_mut().status()),
+                KBDR => return Some(keyboard.borrow_mut().take().map_or(0, u16::from)),
+                _ => {}
+            }
+        }
+        if address == DSR && self.display.is_some() {
+            return Some(DSR_READY);
+        }
+        if address as usize >= self.size {
             return None;
         }
         Some(self.locations[address as usize])
@@ -26,25 +75,90 @@ impl Memory {
 
   
     pub fn write(&mut self, address: u16, value: u16) -> Result<(), LC3Error> {
-        if address as usize >= MEMORY_MAX {
-            return Err(LC3Error::MemoryOutOfBounds);
+        if address == KBSR
+            && let Some(keyboard) = self.keyboard.as_mut()
+        {
+            keyboard.get_mut().set_status(value);
+            return Ok(());
+        }
+        if address == DDR
+            && let Some(display) = self.display.as_mut()
+        {
+            display.write((value & 0xFF) as u8);
+            return Ok(());
+        }
+        if address as usize >= self.size {
+            return Err(LC3Error::InvalidAddress(address));
         }
         self.locations[address as usize] = value;
         Ok(())
     }
 
     pub fn load_program(&mut self, start_address: u16, program: &[u16]) -> Result<usize, LC3Error> {
-        if start_address as usize + program.len() > MEMORY_MAX {
+        if start_address as usize + program.len() > self.size {
             return Err(LC3Error::MemoryOutOfBounds);
         }
 
         for (i, &instruction) in program.iter().enumerate() {
             self.write(start_address + i as u16, instruction)?;
+            self.loaded[start_address as usize + i] = true;
         }
 
         Ok(program.len())
     }
 
+    /// Map `keyboard` onto KBSR/KBDR. Without one those addresses are plain memory,
+    /// which keeps pure computation runs free of device side effects
+    pub fn attach_keyboard(&mut self, keyboard: Keyboard) {
+        self.keyboard = Some(RefCell::new(keyboard));
+    }
+
+    pub fn detach_keyboard(&mut self) -> Option<Keyboard> {
+        self.keyboard.take().map(RefCell::into_inner)
+    }
+
+    pub fn keyboard_mut(&mut self) -> Option<&mut Keyboard> {
+        self.keyboard.as_mut().map(RefCell::get_mut)
+    }
+
+    /// Map a display onto DSR/DDR; like the keyboard it is off unless attached
+    pub fn attach_display(&mut self, display: Display) {
+        self.display = Some(display);
+    }
+
+    pub fn display_mut(&mut self) -> Option<&mut Display> {
+        self.display.as_mut()
+    }
+
+    /// Load an LC-3 `.obj` image and return its origin (see `parse_object`)
+    pub fn load_object_file(&mut self, bytes: &[u8]) -> Result<u16, LC3Error> {
+        let (origin, words) = parse_object(bytes)?;
+        self.load_program(origin, &words)?;
+        Ok(origin)
+    }
+
+    /// Overwrite memory from address 0 with `image`, bypassing devices and protection
+    pub fn restore(&mut self, image: &[u16]) {
+        let len = image.len().min(self.size);
+        self.locations[..len].copy_from_slice(&image[..len]);
+    }
+
+    /// Whether `address` was filled by `load_program` (plain writes don't count)
+    pub fn is_loaded_at(&self, address: u16) -> bool {
+        self.loaded[address as usize]
+    }
+
+    /// Protect the inclusive range `start..=end` from stores executed by the program
+    pub fn mark_readonly(&mut self, start: u16, end: u16) {
+        self.readonly.push((start, end));
+    }
+
+    pub fn is_readonly(&self, address: u16) -> bool {
+        self.readonly
+            .iter()
+            .any(|&(start, end)| (start..=end).contains(&address))
+    }
+
     pub fn fetch_instruction(&self, registers: &mut RegisterFile) -> Option<u16> {
         let pc = registers.get_pc();
         let instruction = self.read(pc)?;
@@ -52,10 +166,146 @@ impl Memory {
         Some(instruction)
     }
 
+    /// Render `len` words from `start`, eight words per line
+    pub fn hexdump(&self, start: u16, len: usize, mode: AddrMode) -> String {
+        let words = self.get_memory_slice(start as usize, len);
+
+        words
+            .chunks(8)
+            .enumerate()
+            .map(|(row, chunk)| {
+                let address = start as usize + row * 8;
+                match mode {
+                    AddrMode::Word => {
+                        let cells: Vec<String> =
+                            chunk.iter().map(|word| format!("{:04X}", word)).collect();
+                        format!("x{:04X}: {}", address, cells.join(" "))
+                    }
+                    AddrMode::Byte => {
+                        let cells: Vec<String> = chunk
+                            .iter()
+                            .flat_map(|word| word.to_be_bytes())
+                            .map(|byte| format!("{:02X}", byte))
+                            .collect();
+                        format!("x{:05X}: {}", address * 2, cells.join(" "))
+                    }
+                }
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// Stored word at `address` without going through the devices, so inspecting
+    /// KBDR doesn't consume a key; None outside the addressable range
+    pub fn peek(&self, address: u16) -> Option<u16> {
+        ((address as usize) < self.size).then(|| self.locations[address as usize])
+    }
+
+    /// Store `value` at `address` without going through the devices, the counterpart
+    /// of `peek` for undoing writes
+    pub fn poke(&mut self, address: u16, value: u16) -> Result<(), LC3Error> {
+        if address as usize >= self.size {
+            return Err(LC3Error::InvalidAddress(address));
+        }
+        self.locations[address as usize] = value;
+        Ok(())
+    }
+
    
     pub fn get_memory_slice(&self, start: usize, len: usize) -> &[u16] {
-        let end = (start + len).min(MEMORY_MAX);
-        &self.locations[start..end]
+        let end = (start + len).min(self.size);
+        &self.locations[start.min(end)..end]
+    }
+}
+
+/// Magic bytes that open an lc3tools object file, followed by its two version bytes
+const LC3TOOLS_MAGIC: [u8; 4] = [0x1C, 0x30, 0x15, 0xC0];
+const LC3TOOLS_VERSION: [u8; 2] = [0x01, 0x01];
+
+/// Split an LC-3 `.obj` image into its origin and program words.
+/// An image starting with the lc3tools magic is read in that format; anything else
+/// must be the plain format, a big-endian origin followed by big-endian words.
+pub fn parse_object(bytes: &[u8]) -> Result<(u16, Vec<u16>), LC3Error> {
+    if let Some(rest) = bytes.strip_prefix(&LC3TOOLS_MAGIC) {
+        return parse_lc3tools_object(rest);
+    }
+    if bytes.len() < 2 || !bytes.len().is_multiple_of(2) {
+        return Err(LC3Error::Custom(format!(
+            "Unrecognized object file format: no lc3tools header, and {} bytes is not a plain \
+             image (an even number of bytes, at least 2)",
+            bytes.len()
+        )));
+    }
+
+    let mut words = bytes
+        .chunks_exact(2)
+        .map(|pair| u16::from_be_bytes([pair[0], pair[1]]));
+    let origin = words.next().unwrap_or(0);
+
+    Ok((origin, words.collect()))
+}
+
+/// lc3tools entries after the magic: a little-endian word, an is-origin flag byte and
+/// the source line as a little-endian u32 length plus text. Only one `.ORIG` block is supported
+fn parse_lc3tools_object(bytes: &[u8]) -> Result<(u16, Vec<u16>), LC3Error> {
+    let error = |msg: &str| LC3Error::Custom(format!("Invalid lc3tools object file: {}", msg));
+    let mut rest = bytes
+        .strip_prefix(&LC3TOOLS_VERSION)
+        .ok_or_else(|| error("unsupported version"))?;
+    let mut origin = None;
+    let mut words = Vec::new();
+
+    while !rest.is_empty() {
+        let [lo, hi, is_orig, l0, l1, l2, l3, tail @ ..] = rest else {
+            return Err(error("truncated entry"));
+        };
+        let value = u16::from_le_bytes([*lo, *hi]);
+        let line_len = u32::from_le_bytes([*l0, *l1, *l2, *l3]) as usize;
+        rest = tail.get(line_len..).ok_or_else(|| error("truncated entry"))?;
+
+        match (*is_orig != 0, origin) {
+            (true, None) => origin = Some(value),
+            (true, Some(_)) => return Err(error("more than one .ORIG block")),
+            (false, None) => return Err(error("word before .ORIG")),
+            (false, Some(_)) => words.push(value),
+        }
+    }
+
+    let origin = origin.ok_or_else(|| error("no .ORIG entry"))?;
+    Ok((origin, words))
+}
+
+/// Read-only, panic-free window onto memory for UI code
+#[derive(Debug, Clone, Copy)]
+pub struct MemoryView<'a> {
+    memory: &'a Memory,
+}
+
+impl<'a> MemoryView<'a> {
+    pub fn new(memory: &'a Memory) -> Self {
+        Self { memory }
+    }
+
+    /// Word at `address`, or 0 outside the addressable range; devices are not touched
+    pub fn get(&self, address: u16) -> u16 {
+        self.memory.peek(address).unwrap_or(0)
+    }
+
+    /// `(address, value)` pairs for up to `len` words from `start`, stopping at the end of memory
+    pub fn range(&self, start: u16, len: usize) -> impl Iterator<Item = (u16, u16)> + 'a {
+        let words = self.memory.get_memory_slice(start as usize, len);
+        words
+            .iter()
+            .enumerate()
+            .map(move |(i, &value)| (start.wrapping_add(i as u16), value))
+    }
+
+    pub fn len(&self) -> usize {
+        self.memory.size()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
     }
 }
 
@@ -64,3 +314,138 @@ impl Default for Memory {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_object_reads_a_plain_image() {
+        let bytes = [0x30, 0x00, 0x12, 0x34, 0xF0, 0x25];
+
+        assert_eq!(parse_object(&bytes).unwrap(), (0x3000, vec![0x1234, 0xF025]));
+    }
+
+    #[test]
+    fn parse_object_reads_an_lc3tools_image() {
+        let mut bytes = vec![0x1C, 0x30, 0x15, 0xC0, 0x01, 0x01];
+        for (value, is_orig, line) in [
+            (0x3000u16, 1u8, ".ORIG x3000"),
+            (0x1234, 0, ""),
+            (0xF025, 0, "HALT"),
+        ] {
+            bytes.extend(value.to_le_bytes());
+            bytes.push(is_orig);
+            bytes.extend((line.len() as u32).to_le_bytes());
+            bytes.extend(line.bytes());
+        }
+
+        assert_eq!(parse_object(&bytes).unwrap(), (0x3000, vec![0x1234, 0xF025]));
+    }
+
+    #[test]
+    fn parse_object_rejects_unrecognized_formats() {
+        let odd = parse_object(&[0x30, 0x00, 0x12]).unwrap_err();
+        let bad_version = parse_object(&[0x1C, 0x30, 0x15, 0xC0, 0x02, 0x00]).unwrap_err();
+
+        assert!(odd.to_string().contains("Unrecognized object file format"));
+        assert_eq!(
+            bad_version,
+            LC3Error::Custom("Invalid lc3tools object file: unsupported version".to_string())
+        );
+    }
+
+    #[test]
+    fn hexdump_word_and_byte_modes_show_the_same_region() {
+        let mut memory = Memory::new();
+        memory.load_program(0x3000, &[0x1234, 0xABCD]).unwrap();
+
+        assert_eq!(memory.hexdump(0x3000, 2, AddrMode::Word), "x3000: 1234 ABCD");
+        assert_eq!(memory.hexdump(0x3000, 2, AddrMode::Byte), "x06000: 12 34 AB CD");
+    }
+
+    #[test]
+    fn memory_view_iterates_a_range() {
+        let mut memory = Memory::with_size(0x3003);
+        memory.load_program(0x3000, &[7, 8, 9]).unwrap();
+        let view = MemoryView::new(&memory);
+
+        let words: Vec<(u16, u16)> = view.range(0x2FFF, 10).collect();
+
+        assert_eq!(words, vec![(0x2FFF, 0), (0x3000, 7), (0x3001, 8), (0x3002, 9)]);
+        assert_eq!(view.get(0x3001), 8);
+        assert_eq!(view.get(0x4000), 0);
+    }
+
+    #[test]
+    fn fetch_at_xffff_wraps_pc_to_x0000() {
+        let mut memory = Memory::new();
+        let mut registers = RegisterFile::new();
+        memory.write(0xFFFF, 0x1021).unwrap();
+        registers.set_pc(0xFFFF).unwrap();
+
+        assert_eq!(memory.fetch_instruction(&mut registers), Some(0x1021));
+        assert_eq!(registers.get_pc(), 0x0000);
+    }
+
+    #[test]
+    fn keyboard_registers_are_mapped_only_when_attached() {
+        let mut memory = Memory::new();
+        memory.write(KBSR, 0x1234).unwrap();
+        assert_eq!(memory.read(KBSR), Some(0x1234));
+        assert_eq!(memory.read(KBDR), Some(0));
+
+        let mut keyboard = Keyboard::new();
+        keyboard.press(b'k');
+        memory.attach_keyboard(keyboard);
+
+        assert_eq!(memory.read(KBSR), Some(0x8000));
+        assert_eq!(memory.read(KBDR), Some(u16::from(b'k')));
+        assert_eq!(memory.read(KBSR), Some(0));
+
+        memory.detach_keyboard();
+        assert_eq!(memory.read(KBSR), Some(0x1234));
+    }
+
+    #[test]
+    fn memory_fits_on_a_small_thread_stack() {
+        let handle = std::thread::Builder::new()
+            .stack_size(64 * 1024)
+            .spawn(|| {
+                let mut memory = Memory::new();
+                memory.write(0xFFFF, 7).unwrap();
+                memory.read(0xFFFF)
+            })
+            .unwrap();
+
+        assert_eq!(handle.join().unwrap(), Some(7));
+    }
+
+    #[test]
+    fn viewing_kbdr_leaves_the_key_pending() {
+        let mut memory = Memory::new();
+        let mut keyboard = Keyboard::new();
+        keyboard.press(b'a');
+        memory.attach_keyboard(keyboard);
+
+        let view = MemoryView::new(&memory);
+        assert_eq!(view.get(KBDR), 0);
+        assert_eq!(view.get(KBDR), 0);
+
+        assert_eq!(memory.read(KBSR), Some(0x8000));
+        assert_eq!(memory.read(KBDR), Some(u16::from(b'a')));
+    }
+
+    #[test]
+    fn load_object_file_places_words_at_the_origin() {
+        let mut memory = Memory::new();
+
+        let origin = memory.load_object_file(&[0x40, 0x00, 0x12, 0x34, 0xF0, 0x25]).unwrap();
+
+        assert_eq!(origin, 0x4000);
+        assert_eq!(memory.get_memory_slice(0x4000, 2), [0x1234, 0xF025]);
+        assert!(memory.is_loaded_at(0x4001));
+        assert!(!memory.is_loaded_at(0x4002));
+        assert!(memory.load_object_file(&[0x40]).is_err());
+    }
+}
@@ -0,0 +1,55 @@
+//! Ready-to-load program skeletons for tests and demos.
+//! Every program is PC-relative, so it can be loaded at any origin.
+
+use crate::types::{Opcodes, TrapVectors};
+
+fn trap(vector: TrapVectors) -> u16 {
+    (Opcodes::TRAP.to_u16() << 12) | vector.to_u16()
+}
+
+/// Load `a` and `b`, add them, leave the sum in R0 and halt
+pub fn add_two(a: u16, b: u16) -> Vec<u16> {
+    vec![
+        (Opcodes::LD.to_u16() << 12) | 3,            // LD R0, A
+        (Opcodes::LD.to_u16() << 12) | (1 << 9) | 3, // LD R1, B
+        (Opcodes::ADD.to_u16() << 12) | 1,           // ADD R0, R0, R1
+        trap(TrapVectors::HALT),
+        a,
+        b,
+    ]
+}
+
+/// Print `s` with PUTS and halt; the string is stored one character per word
+pub fn print_string(s: &str) -> Vec<u16> {
+    let mut program = vec![
+        (Opcodes::LEA.to_u16() << 12) | 2, // LEA R0, STRING
+        trap(TrapVectors::PUTS),
+        trap(TrapVectors::HALT),
+    ];
+    program.extend(s.bytes().map(u16::from));
+    program.push(0);
+    program
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::Registers;
+    use crate::vm::{HaltStatus, LC3VM};
+
+    #[test]
+    fn add_two_leaves_the_sum_in_r0() {
+        let report = LC3VM::execute(&add_two(40, 2), 0x3000, b"", 100).unwrap();
+
+        assert_eq!(report.stop_reason, HaltStatus::HaltedCleanly);
+        assert_eq!(report.registers[Registers::R0 as usize], 42);
+    }
+
+    #[test]
+    fn print_string_prints_the_string() {
+        let report = LC3VM::execute(&print_string("hi!"), 0x4000, b"", 100).unwrap();
+
+        assert_eq!(report.stop_reason, HaltStatus::HaltedCleanly);
+        assert_eq!(report.output, b"hi!");
+    }
+}
@@ -57,7 +57,7 @@ impl RegisterFile {
     
     pub fn increment_pc(&mut self) -> Result<(), LC3Error> {
         let current_pc = self.get_pc();
-        self.set_pc(current_pc + 1)
+        self.set_pc(current_pc.wrapping_add(1))
     }
 
     
@@ -65,6 +65,11 @@ impl RegisterFile {
         self.read(Registers::COND).unwrap_or(0)
     }
 
+    /// Copy of every register slot, indexed by `Registers as usize`
+    pub fn snapshot(&self) -> [u16; REG_COUNT] {
+        self.locations
+    }
+
     
     pub fn is_flag_set(&self, flag: Flags) -> bool {
         (self.get_condition_code() & flag as u16) != 0
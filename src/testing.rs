@@ -0,0 +1,95 @@
+use crate::io::BufferIo;
+use crate::types::{PC_START, Registers};
+use crate::vm::LC3VM;
+
+/// Step budget for harness runs, so a looping program fails instead of hanging
+pub const MAX_STEPS: u64 = 1_000_000;
+
+/// What a reference model says a program should do for a given input
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Expected {
+    /// Bytes written by the traps
+    pub output: Vec<u8>,
+    /// Final values of the registers the model cares about; others are not checked
+    pub registers: Vec<(Registers, u16)>,
+}
+
+/// Run `program` (loaded at `PC_START`) on `input` and panic at the first place its
+/// output or final registers differ from `reference(input)`
+pub fn assert_same_behavior(program: &[u16], input: &[u8], reference: impl Fn(&[u8]) -> Expected) {
+    let expected = reference(input);
+
+    let mut vm = LC3VM::new();
+    let io = BufferIo::new(input);
+    let output = io.output_handle();
+    vm.set_io(Box::new(io));
+    vm.initialize(PC_START, program)
+        .unwrap_or_else(|e| panic!("failed to load program: {}", e));
+
+    if let Err(msg) = vm.run_for(MAX_STEPS) {
+        panic!("program errored at PC 0x{:04X}: {}", vm.get_pc(), msg);
+    }
+    if vm.is_running() {
+        panic!("program did not halt within {} steps", MAX_STEPS);
+    }
+
+    let actual = output.borrow();
+    if let Some(index) = (0..actual.len().max(expected.output.len()))
+        .find(|&i| actual.get(i) != expected.output.get(i))
+    {
+        panic!(
+            "output differs at byte {}: VM wrote {:?}, reference expected {:?}\nVM output: {:?}",
+            index,
+            actual.get(index),
+            expected.output.get(index),
+            String::from_utf8_lossy(&actual)
+        );
+    }
+
+    for &(reg, want) in &expected.registers {
+        let got = vm.register(reg);
+        if got != want {
+            panic!(
+                "register {:?} differs: VM has 0x{:04X}, reference expected 0x{:04X}",
+                reg, got, want
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assembler::assemble;
+
+    const ECHO: &str = ".ORIG x3000
+            GETC
+            OUT
+            HALT
+        .END";
+
+    fn echo_reference(input: &[u8]) -> Expected {
+        Expected {
+            output: input[..1].to_vec(),
+            registers: vec![(Registers::R0, u16::from(input[0]))],
+        }
+    }
+
+    #[test]
+    fn matching_reference_passes() {
+        let (_, program) = assemble(ECHO).unwrap();
+
+        assert_same_behavior(&program, b"q", echo_reference);
+    }
+
+    #[test]
+    #[should_panic(expected = "output differs at byte 0")]
+    fn differing_output_panics() {
+        let (_, program) = assemble(ECHO).unwrap();
+
+        assert_same_behavior(&program, b"q", |_| Expected {
+            output: b"x".to_vec(),
+            registers: Vec::new(),
+        });
+    }
+}
@@ -0,0 +1,76 @@
+/// One executed instruction as captured by the VM's trace buffer
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TraceRecord {
+    /// Value of the instruction counter before this instruction ran
+    pub step: u64,
+    /// Address the instruction was fetched from
+    pub pc: u16,
+    /// Raw instruction word
+    pub instruction: u16,
+}
+
+/// Where two traces first disagree on PC or instruction word
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Divergence {
+    /// Index into both traces
+    pub index: usize,
+    /// Record from the first trace, `None` if it ended early
+    pub left: Option<TraceRecord>,
+    /// Record from the second trace, `None` if it ended early
+    pub right: Option<TraceRecord>,
+}
+
+/// Find the first step where two runs executed different code.
+/// A trace that is a strict prefix of the other diverges where it ends.
+pub fn first_divergence(left: &[TraceRecord], right: &[TraceRecord]) -> Option<Divergence> {
+    let same = |a: &TraceRecord, b: &TraceRecord| a.pc == b.pc && a.instruction == b.instruction;
+
+    (0..left.len().max(right.len())).find_map(|index| {
+        let (a, b) = (left.get(index), right.get(index));
+        match (a, b) {
+            (Some(a), Some(b)) if same(a, b) => None,
+            _ => Some(Divergence {
+                index,
+                left: a.copied(),
+                right: b.copied(),
+            }),
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assembler::assemble;
+    use crate::types::Registers;
+    use crate::vm::LC3VM;
+
+    fn trace_with_r0(r0: u16) -> Vec<TraceRecord> {
+        let (origin, program) = assemble(
+            ".ORIG x3000
+                ADD R1, R1, #1
+                ADD R0, R0, #0
+                BRz SKIP
+                ADD R1, R1, #1
+            SKIP HALT
+            .END",
+        )
+        .unwrap();
+        let mut vm = LC3VM::new();
+        vm.initialize(origin, &program).unwrap();
+        vm.set_register(Registers::R0, r0).unwrap();
+        vm.enable_trace_buffer(16);
+        vm.run().unwrap();
+        vm.trace_buffer()
+    }
+
+    #[test]
+    fn runs_diverge_where_the_branch_goes_differently() {
+        let divergence = first_divergence(&trace_with_r0(0), &trace_with_r0(1)).unwrap();
+
+        assert_eq!(divergence.index, 3);
+        assert_eq!(divergence.left.map(|record| record.pc), Some(0x3004));
+        assert_eq!(divergence.right.map(|record| record.pc), Some(0x3003));
+        assert_eq!(first_divergence(&trace_with_r0(1), &trace_with_r0(1)), None);
+    }
+}
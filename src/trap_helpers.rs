@@ -0,0 +1,89 @@
+//! Argument helpers for custom trap handlers (see `LC3VM::on_unknown_trap`).
+//! They follow the standard trap conventions: arguments and results in R0,
+//! strings stored one character per word and null-terminated.
+
+use crate::memory::Memory;
+use crate::registers::RegisterFile;
+use crate::types::{DEFAULT_MAX_STRING_LENGTH, LC3Error, Registers};
+
+pub fn read_r0(registers: &RegisterFile) -> u16 {
+    registers.read(Registers::R0).unwrap_or(0)
+}
+
+pub fn write_r0(registers: &mut RegisterFile, value: u16) {
+    let _ = registers.write(Registers::R0, value);
+}
+
+/// The null-terminated string R0 points to, using the low byte of each word.
+/// Stops early at the end of memory or after `DEFAULT_MAX_STRING_LENGTH` characters.
+pub fn read_r0_string(memory: &Memory, registers: &RegisterFile) -> String {
+    let start = read_r0(registers);
+
+    (0..DEFAULT_MAX_STRING_LENGTH)
+        .map_while(|i| start.checked_add(i as u16).and_then(|a| memory.read(a)))
+        .take_while(|&word| word != 0)
+        .map(|word| (word & 0xFF) as u8 as char)
+        .collect()
+}
+
+/// Store `text` at the address in R0, one character per word, plus a null terminator
+pub fn write_r0_string(
+    memory: &mut Memory,
+    registers: &RegisterFile,
+    text: &str,
+) -> Result<(), LC3Error> {
+    let start = read_r0(registers);
+
+    for (i, word) in text.bytes().map(u16::from).chain([0]).enumerate() {
+        let address = start
+            .checked_add(i as u16)
+            .ok_or(LC3Error::MemoryOutOfBounds)?;
+        memory.write(address, word)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assembler::assemble;
+    use crate::instructions::ExecutionResult;
+    use crate::vm::LC3VM;
+
+    fn uppercase_trap(
+        _: u16,
+        memory: &mut Memory,
+        registers: &mut RegisterFile,
+    ) -> ExecutionResult {
+        let text = read_r0_string(memory, registers).to_ascii_uppercase();
+        match write_r0_string(memory, registers, &text) {
+            Ok(()) => {
+                write_r0(registers, text.len() as u16);
+                ExecutionResult::Continue
+            }
+            Err(e) => ExecutionResult::Error(e.to_string()),
+        }
+    }
+
+    #[test]
+    fn custom_trap_uppercases_a_string_in_place() {
+        let (origin, words) = assemble(
+            ".ORIG x3000
+                LEA R0, TEXT
+                TRAP x30
+                HALT
+            TEXT .STRINGZ \"hello, lc3\"
+            .END",
+        )
+        .unwrap();
+        let mut vm = LC3VM::new();
+        vm.initialize(origin, &words).unwrap();
+        vm.on_unknown_trap(uppercase_trap);
+
+        vm.run().unwrap();
+
+        assert_eq!(vm.register(Registers::R0), 10);
+        vm.set_register(Registers::R0, 0x3003).unwrap();
+        assert_eq!(read_r0_string(&vm.memory, &vm.registers), "HELLO, LC3");
+    }
+}
@@ -13,6 +13,33 @@ pub const REG_COUNT: usize = 11;
 /// Starting address for programs
 pub const PC_START: u16 = 0x3000;
 
+/// Default cap on the characters PUTS/PUTSP will print from one string
+pub const DEFAULT_MAX_STRING_LENGTH: usize = 16384;
+
+/// First address of the memory-mapped device registers
+pub const DEVICE_SPACE_START: u16 = 0xFE00;
+
+/// Keyboard status register: bit 15 set while a key is waiting
+pub const KBSR: u16 = 0xFE00;
+
+/// Keyboard data register: reading it takes the waiting key
+pub const KBDR: u16 = 0xFE02;
+
+/// KBSR bit 15: a key is waiting in KBDR
+pub const KBSR_READY: u16 = 0x8000;
+
+/// KBSR bit 14: raise `KEYBOARD_INTERRUPT` whenever a key is waiting
+pub const KBSR_INTERRUPT_ENABLE: u16 = 0x4000;
+
+/// Display status register: bit 15 set when DDR can take a character
+pub const DSR: u16 = 0xFE04;
+
+/// Display data register: storing to it prints the low byte
+pub const DDR: u16 = 0xFE06;
+
+/// DSR bit 15: the display is ready for the next character
+pub const DSR_READY: u16 = 0x8000;
+
 // ============================================================================
 // REGISTERS
 // ============================================================================
@@ -43,6 +70,25 @@ impl Registers {
     pub fn count() -> usize {
         REG_COUNT
     }
+
+    /// Register named by a 3-bit instruction field; anything above R7 is `InvalidRegister`
+    pub fn try_from_field(value: u16) -> Result<Registers, LC3Error> {
+        match value {
+            0..=7 => Ok(Registers::from(value)),
+            _ => Err(LC3Error::InvalidRegister(value)),
+        }
+    }
+}
+
+impl std::fmt::Display for Registers {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Registers::PC => write!(f, "PC"),
+            Registers::COND => write!(f, "COND"),
+            Registers::COUNT => write!(f, "COUNT"),
+            reg => write!(f, "R{}", *reg as u16),
+        }
+    }
 }
 
 // ============================================================================
@@ -64,6 +110,24 @@ impl Flags {
     pub fn is_set_in(&self, condition_code: u16) -> bool {
         (condition_code & (*self as u16)) != 0
     }
+
+    /// Condition code as `nzp`, with `.` for each clear bit (e.g. `n.p`)
+    pub fn describe(condition_code: u16) -> String {
+        [(Flags::NEG, 'n'), (Flags::ZRO, 'z'), (Flags::POS, 'p')]
+            .iter()
+            .map(|&(flag, c)| if flag.is_set_in(condition_code) { c } else { '.' })
+            .collect()
+    }
+}
+
+impl std::fmt::Display for Flags {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Flags::POS => write!(f, "P"),
+            Flags::ZRO => write!(f, "Z"),
+            Flags::NEG => write!(f, "N"),
+        }
+    }
 }
 
 // ============================================================================
@@ -166,6 +230,19 @@ impl Opcodes {
     }
 }
 
+/// Parse a plain mnemonic such as `"ldr"`, ignoring case. Branch forms like `BRnzp`
+/// are left to the assembler; a failure is `InvalidOpcode(0xFFFF)` as no opcode matched
+impl std::str::FromStr for Opcodes {
+    type Err = LC3Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        (0..16)
+            .filter_map(Opcodes::from_u16)
+            .find(|opcode| opcode.to_string().eq_ignore_ascii_case(s))
+            .ok_or(LC3Error::InvalidOpcode(u16::MAX))
+    }
+}
+
 // ============================================================================
 // TRAP VECTORS
 // ============================================================================
@@ -226,6 +303,48 @@ impl TrapVectors {
     }
 }
 
+/// Parse a trap alias such as `"halt"`, ignoring case; a failure is `InvalidTrapVector(0xFFFF)`
+impl std::str::FromStr for TrapVectors {
+    type Err = LC3Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        (0x20..=0x25)
+            .filter_map(TrapVectors::from_u16)
+            .find(|trap| trap.to_string().eq_ignore_ascii_case(s))
+            .ok_or(LC3Error::InvalidTrapVector(u16::MAX))
+    }
+}
+
+/// Non-standard trap vectors, only dispatched when extended traps are enabled
+/// GETS: read a line into memory at R0 (at most R1 chars, null-terminated), count in R0
+pub const TRAP_GETS: u16 = 0x26;
+/// PUTHEX: print R0 as four uppercase hex digits
+pub const TRAP_PUTHEX: u16 = 0x27;
+
+// ============================================================================
+// PROCESSOR STATUS AND INTERRUPTS
+// ============================================================================
+
+/// PSR bit 15: set in user mode, clear in supervisor mode
+pub const PSR_USER_MODE: u16 = 0x8000;
+/// PSR bits 10:8 hold the priority level of the running code
+pub const PSR_PRIORITY_MASK: u16 = 0x0700;
+/// Base of the interrupt vector table; vector `v` is dispatched through `memory[0x0100 + v]`
+pub const INTERRUPT_VECTOR_TABLE: u16 = 0x0100;
+/// Exception vector raised by RTI (or other privileged work) in user mode
+pub const PRIVILEGE_MODE_EXCEPTION: u16 = 0x00;
+/// Exception vector raised when user-mode code loads or stores outside user space
+/// (below `PC_START` or in device space)
+pub const ACCESS_CONTROL_VIOLATION: u16 = 0x02;
+/// Interrupt vector of the keyboard, dispatched through `memory[0x0180]`
+pub const KEYBOARD_INTERRUPT: u16 = 0x80;
+/// Priority level the keyboard interrupts at; it only fires over lower-priority code
+pub const KEYBOARD_PRIORITY: u16 = 4;
+/// Priority level the `LC3VM::run_with_timer` clock interrupts at
+pub const TIMER_PRIORITY: u16 = 6;
+/// Supervisor stack pointer loaded into R6 on the first switch to supervisor mode
+pub const DEFAULT_SUPERVISOR_STACK: u16 = 0x3000;
+
 // ============================================================================
 // INSTRUCTION EXTRACTION FUNCTIONS
 // ============================================================================
@@ -290,6 +409,27 @@ pub fn extract_trap_vector(instruction: u16) -> u16 {
     instruction & 0xFF
 }
 
+/// Look up an instruction field by name, returning its raw (not sign-extended) bits
+/// Known names: opcode, dr, sr, sr1, baser, sr2, imm5flag, imm5, nzp, pcoffset9,
+/// pcoffset11, offset6, trapvect8; anything else returns None
+pub fn field(instruction: u16, name: &str) -> Option<u16> {
+    let value = match name.to_ascii_lowercase().as_str() {
+        "opcode" => extract_opcode(instruction),
+        "dr" | "sr" => extract_dr(instruction),
+        "sr1" | "baser" => extract_sr1(instruction),
+        "sr2" => extract_sr2(instruction),
+        "imm5flag" => extract_imm5_flag(instruction) as u16,
+        "imm5" => extract_imm5(instruction),
+        "nzp" => (instruction >> 9) & 0x7,
+        "pcoffset9" => extract_pc_offset9(instruction),
+        "pcoffset11" => extract_pc_offset11(instruction),
+        "offset6" => extract_offset6(instruction),
+        "trapvect8" => extract_trap_vector(instruction),
+        _ => return None,
+    };
+    Some(value)
+}
+
 // ============================================================================
 // SIGN EXTENSION FUNCTIONS
 // ============================================================================
@@ -340,6 +480,83 @@ pub fn sign_extend(value: u16, bit_count: usize) -> u16 {
     }
 }
 
+// ============================================================================
+// DECODED INSTRUCTIONS
+// ============================================================================
+
+/// Second operand of ADD and AND
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AddSource {
+    Register(Registers),
+    /// Sign-extended imm5
+    Immediate(i16),
+}
+
+/// An instruction word split into its fields; offsets are already sign-extended
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Instruction {
+    Br { n: bool, z: bool, p: bool, offset: i16 },
+    Add { dr: Registers, sr1: Registers, src2: AddSource },
+    Ld { dr: Registers, offset: i16 },
+    St { sr: Registers, offset: i16 },
+    Jsr { offset: i16 },
+    Jsrr { base: Registers },
+    And { dr: Registers, sr1: Registers, src2: AddSource },
+    Ldr { dr: Registers, base: Registers, offset6: i16 },
+    Str { sr: Registers, base: Registers, offset6: i16 },
+    Rti,
+    Not { dr: Registers, sr: Registers },
+    Ldi { dr: Registers, offset: i16 },
+    Sti { sr: Registers, offset: i16 },
+    /// Also covers RET (`base` = R7)
+    Jmp { base: Registers },
+    Lea { dr: Registers, offset: i16 },
+    Trap { vector: u8 },
+}
+
+/// Decode an instruction word; the reserved opcode is `LC3Error::InvalidOpcode`
+pub fn decode(word: u16) -> Result<Instruction, LC3Error> {
+    let opcode = extract_opcode(word);
+    let dr = Registers::try_from_field(extract_dr(word))?;
+    let sr1 = Registers::try_from_field(extract_sr1(word))?;
+    let offset9 = sign_extend_pc_offset9(extract_pc_offset9(word)) as i16;
+    let offset6 = sign_extend_offset6(extract_offset6(word)) as i16;
+    let src2 = if extract_imm5_flag(word) {
+        AddSource::Immediate(sign_extend_imm5(extract_imm5(word)) as i16)
+    } else {
+        AddSource::Register(Registers::try_from_field(extract_sr2(word))?)
+    };
+
+    let instruction = match Opcodes::from_u16(opcode).ok_or(LC3Error::InvalidOpcode(opcode))? {
+        Opcodes::BR => Instruction::Br {
+            n: word & 0x0800 != 0,
+            z: word & 0x0400 != 0,
+            p: word & 0x0200 != 0,
+            offset: offset9,
+        },
+        Opcodes::ADD => Instruction::Add { dr, sr1, src2 },
+        Opcodes::LD => Instruction::Ld { dr, offset: offset9 },
+        Opcodes::ST => Instruction::St { sr: dr, offset: offset9 },
+        Opcodes::JSR if word & 0x0800 != 0 => Instruction::Jsr {
+            offset: sign_extend_pc_offset11(extract_pc_offset11(word)) as i16,
+        },
+        Opcodes::JSR => Instruction::Jsrr { base: sr1 },
+        Opcodes::AND => Instruction::And { dr, sr1, src2 },
+        Opcodes::LDR => Instruction::Ldr { dr, base: sr1, offset6 },
+        Opcodes::STR => Instruction::Str { sr: dr, base: sr1, offset6 },
+        Opcodes::RTI => Instruction::Rti,
+        Opcodes::NOT => Instruction::Not { dr, sr: sr1 },
+        Opcodes::LDI => Instruction::Ldi { dr, offset: offset9 },
+        Opcodes::STI => Instruction::Sti { sr: dr, offset: offset9 },
+        Opcodes::JMP => Instruction::Jmp { base: sr1 },
+        Opcodes::RES => return Err(LC3Error::InvalidOpcode(opcode)),
+        Opcodes::LEA => Instruction::Lea { dr, offset: offset9 },
+        Opcodes::TRAP => Instruction::Trap { vector: extract_trap_vector(word) as u8 },
+    };
+
+    Ok(instruction)
+}
+
 // ============================================================================
 // CONVERSION IMPLEMENTATIONS
 // ============================================================================
@@ -389,8 +606,8 @@ pub enum LC3Error {
     MemoryOutOfBounds,
     /// Register access out of bounds
     RegisterOutOfBounds,
-    /// IO error
-    IoError(ErrorKind),
+    /// IO error, with the message of the underlying `std::io::Error`
+    IoError(ErrorKind, String),
     /// Custom error message
     Custom(String),
 }
@@ -404,7 +621,7 @@ impl std::fmt::Display for LC3Error {
             LC3Error::InvalidTrapVector(vector) => write!(f, "Invalid trap vector: 0x{:02X}", vector),
             LC3Error::MemoryOutOfBounds => write!(f, "Memory access out of bounds"),
             LC3Error::RegisterOutOfBounds => write!(f, "Register access out of bounds"),
-            LC3Error::IoError(kind) => write!(f, "IO error: {:?}", kind),
+            LC3Error::IoError(kind, msg) => write!(f, "IO error ({:?}): {}", kind, msg),
             LC3Error::Custom(msg) => write!(f, "{}", msg),
         }
     }
@@ -414,7 +631,25 @@ impl std::error::Error for LC3Error {}
 
 impl From<ErrorKind> for LC3Error {
     fn from(kind: ErrorKind) -> Self {
-        LC3Error::IoError(kind)
+        LC3Error::IoError(kind, kind.to_string())
+    }
+}
+
+/// Keeps the error kind and message; the source error itself is dropped so
+/// `LC3Error` can stay `Clone + Eq`
+impl From<std::io::Error> for LC3Error {
+    fn from(err: std::io::Error) -> Self {
+        LC3Error::IoError(err.kind(), err.to_string())
+    }
+}
+
+/// `IoError` maps back to its kind and message, everything else becomes `ErrorKind::Other`
+impl From<LC3Error> for std::io::Error {
+    fn from(err: LC3Error) -> Self {
+        match err {
+            LC3Error::IoError(kind, msg) => std::io::Error::new(kind, msg),
+            other => std::io::Error::other(other),
+        }
     }
 }
 
@@ -429,3 +664,99 @@ impl From<&str> for LC3Error {
         LC3Error::Custom(msg.to_string())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decode_splits_fields() {
+        // ADD R1, R2, #-3
+        assert_eq!(
+            decode(0x12BD),
+            Ok(Instruction::Add {
+                dr: Registers::R1,
+                sr1: Registers::R2,
+                src2: AddSource::Immediate(-3),
+            })
+        );
+        // BRnp #-2
+        assert_eq!(
+            decode(0x0BFE),
+            Ok(Instruction::Br { n: true, z: false, p: true, offset: -2 })
+        );
+        // LDR R3, R6, #-1
+        assert_eq!(
+            decode(0x67BF),
+            Ok(Instruction::Ldr { dr: Registers::R3, base: Registers::R6, offset6: -1 })
+        );
+        assert_eq!(decode(0x4FFF), Ok(Instruction::Jsr { offset: -1 }));
+        assert_eq!(decode(0x4180), Ok(Instruction::Jsrr { base: Registers::R6 }));
+        assert_eq!(decode(0xF025), Ok(Instruction::Trap { vector: 0x25 }));
+    }
+
+    #[test]
+    fn try_from_field_rejects_non_operand_registers() {
+        assert_eq!(Registers::try_from_field(0), Ok(Registers::R0));
+        assert_eq!(Registers::try_from_field(7), Ok(Registers::R7));
+        assert_eq!(Registers::try_from_field(8), Err(LC3Error::InvalidRegister(8)));
+        assert_eq!(Registers::try_from_field(10), Err(LC3Error::InvalidRegister(10)));
+    }
+
+    #[test]
+    fn decode_rejects_res() {
+        assert_eq!(decode(0xD000), Err(LC3Error::InvalidOpcode(13)));
+    }
+
+    #[test]
+    fn io_errors_convert_both_ways_keeping_kind_and_message() {
+        let err = std::io::Error::new(ErrorKind::UnexpectedEof, "short read");
+
+        let lc3: LC3Error = err.into();
+        assert_eq!(lc3, LC3Error::IoError(ErrorKind::UnexpectedEof, "short read".to_string()));
+        assert_eq!(lc3.to_string(), "IO error (UnexpectedEof): short read");
+
+        let back: std::io::Error = lc3.into();
+        assert_eq!(back.kind(), ErrorKind::UnexpectedEof);
+        assert_eq!(back.to_string(), "short read");
+        let other: std::io::Error = LC3Error::MemoryOutOfBounds.into();
+        assert_eq!(other.kind(), ErrorKind::Other);
+    }
+
+    #[test]
+    fn field_reads_named_parts_of_add_immediate() {
+        // ADD R3, R1, #-2
+        let instruction = 0x167E;
+
+        assert_eq!(field(instruction, "dr"), Some(3));
+        assert_eq!(field(instruction, "imm5"), Some(0x1E));
+        assert_eq!(field(instruction, "IMM5FLAG"), Some(1));
+        assert_eq!(field(instruction, "opcode"), Some(Opcodes::ADD as u16));
+        assert_eq!(field(instruction, "bogus"), None);
+    }
+
+    #[test]
+    fn registers_and_flags_display() {
+        assert_eq!(Registers::R3.to_string(), "R3");
+        assert_eq!(Registers::PC.to_string(), "PC");
+        assert_eq!(Registers::COND.to_string(), "COND");
+        assert_eq!(Flags::NEG.to_string(), "N");
+        assert_eq!(Flags::ZRO.to_string(), "Z");
+        assert_eq!(Flags::POS.to_string(), "P");
+        assert_eq!(Flags::describe(5), "n.p");
+        assert_eq!(Flags::describe(Flags::ZRO as u16), ".z.");
+        assert_eq!(Flags::describe(0), "...");
+    }
+
+    #[test]
+    fn opcodes_and_trap_vectors_parse_case_insensitively() {
+        assert_eq!("add".parse::<Opcodes>(), Ok(Opcodes::ADD));
+        assert_eq!("Ldr".parse::<Opcodes>(), Ok(Opcodes::LDR));
+        assert_eq!("TRAP".parse::<Opcodes>(), Ok(Opcodes::TRAP));
+        assert_eq!("bogus".parse::<Opcodes>(), Err(LC3Error::InvalidOpcode(u16::MAX)));
+
+        assert_eq!("halt".parse::<TrapVectors>(), Ok(TrapVectors::HALT));
+        assert_eq!("PutS".parse::<TrapVectors>(), Ok(TrapVectors::PUTS));
+        assert_eq!("getx".parse::<TrapVectors>(), Err(LC3Error::InvalidTrapVector(u16::MAX)));
+    }
+}
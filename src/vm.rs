@@ -1,10 +1,347 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
+use std::io::Write;
+use std::path::Path;
+
+use crate::assembler::AssembledProgram;
+use crate::disassembler::disassemble;
+use crate::devices::{Display, Keyboard};
+use crate::io::{BufferIo, Io, StdIo};
 use crate::registers::RegisterFile;
-use crate::memory::Memory;
-use crate::instructions::{InstructionExecutor, ExecutionResult};
-use crate::types::Registers;
+use crate::memory::{Memory, MemoryView, parse_object};
+use crate::trace::TraceRecord;
+use crate::warnings::{Warning, WarningHandler};
+use crate::instructions::{
+    InstructionExecutor, ExecutionResult, ResPolicy, effective_address, is_store,
+    sets_condition_codes,
+};
+use crate::types::{
+    ACCESS_CONTROL_VIOLATION, DEFAULT_MAX_STRING_LENGTH, DEFAULT_SUPERVISOR_STACK,
+    DEVICE_SPACE_START, Flags, INTERRUPT_VECTOR_TABLE, KEYBOARD_INTERRUPT, KEYBOARD_PRIORITY,
+    LC3Error, Opcodes, PC_START, PRIVILEGE_MODE_EXCEPTION, PSR_PRIORITY_MASK, PSR_USER_MODE,
+    REG_COUNT, Registers, TIMER_PRIORITY, TRAP_GETS, TRAP_PUTHEX, TrapVectors, extract_dr,
+    extract_offset6, extract_opcode, extract_sr1, extract_trap_vector, sign_extend_offset6,
+};
+
+/// Summary of a profiling run produced by `LC3VM::run_with_stats`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RunStats {
+    /// Total instructions executed during the run
+    pub instructions_executed: u64,
+    /// Executed instruction count indexed by opcode value
+    pub opcode_counts: [u64; 16],
+    /// BR instructions whose condition held
+    pub branches_taken: u64,
+    /// BR instructions that fell through
+    pub branches_not_taken: u64,
+    /// Data memory reads (instruction fetches are not counted)
+    pub memory_reads: u64,
+    /// Data memory writes
+    pub memory_writes: u64,
+    /// Deepest JSR/JSRR nesting reached (RET pops a level)
+    pub max_stack_depth: usize,
+}
+
+impl RunStats {
+    pub fn opcode_count(&self, opcode: Opcodes) -> u64 {
+        self.opcode_counts[opcode.to_u16() as usize]
+    }
+}
+
+/// Why a bounded run loop returned
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RunOutcome {
+    /// The stop condition the caller asked for was reached
+    ConditionMet,
+    /// The program executed HALT (or was already stopped)
+    Halted,
+    /// The step budget ran out first
+    StepLimitReached,
+    /// An instruction failed with the given message
+    Error(String),
+}
+
+/// Everything a one-shot `LC3VM::execute` run produced
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExecutionReport {
+    /// Bytes written by the program
+    pub output: Vec<u8>,
+    /// Final register file, indexed by `Registers` (PC and COND included)
+    pub registers: [u16; REG_COUNT],
+    /// How the run ended
+    pub stop_reason: HaltStatus,
+    /// Instructions executed
+    pub instruction_count: u64,
+}
+
+/// Why `LC3VM::run_until_breakpoint` returned
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BreakReason {
+    /// PC reached this breakpoint; the instruction there has not run yet
+    Breakpoint(u16),
+    /// The program executed HALT (or was already stopped)
+    Halted,
+    /// `BREAKPOINT_STEP_LIMIT` instructions ran without another stop
+    MaxSteps,
+    /// A store wrote `new` over `old` at the watched address `addr`
+    Watchpoint { addr: u16, old: u16, new: u16 },
+}
+
+/// Instructions `LC3VM::run_until_breakpoint` runs before giving up with `BreakReason::MaxSteps`
+pub const BREAKPOINT_STEP_LIMIT: u64 = 1_000_000;
+
+/// Which guard stopped `LC3VM::run_safely`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SafeRunOutcome {
+    /// The program executed HALT
+    Halted,
+    /// The instruction at `pc` jumps to itself, so the program can never make progress
+    SelfLoop { pc: u16 },
+    /// `ZERO_RUN_LIMIT` zero words in a row were executed, starting at `start`
+    ZeroRun { start: u16 },
+    /// The step budget ran out with the program still running
+    StepLimitReached,
+    /// An instruction failed with the given message
+    Error(String),
+}
+
+impl std::fmt::Display for SafeRunOutcome {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SafeRunOutcome::Halted => write!(f, "program halted normally"),
+            SafeRunOutcome::SelfLoop { pc } => {
+                write!(f, "infinite loop: instruction at 0x{:04X} jumps to itself", pc)
+            }
+            SafeRunOutcome::ZeroRun { start } => write!(
+                f,
+                "ran into empty memory: executing zero words from 0x{:04X} (missing HALT?)",
+                start
+            ),
+            SafeRunOutcome::StepLimitReached => {
+                write!(f, "step limit reached without HALT (long or infinite loop?)")
+            }
+            SafeRunOutcome::Error(msg) => write!(f, "error: {}", msg),
+        }
+    }
+}
+
+/// Consecutive 0x0000 instructions `LC3VM::run_safely` tolerates before giving up
+pub const ZERO_RUN_LIMIT: u64 = 16;
+
+/// Whether the VM is still running and, if not, how it stopped
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum HaltStatus {
+    /// No program has been initialized yet
+    #[default]
+    NotStarted,
+    Running,
+    /// Stopped by HALT or an explicit `halt()`
+    HaltedCleanly,
+    /// Stopped by an instruction or fetch error
+    Errored(LC3Error),
+    /// A bounded run used up its step budget with the program still running
+    StepLimitReached,
+}
+
+/// Everything known about the currently loaded program
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProgramLayout {
+    /// Address the program was loaded at
+    pub origin: u16,
+    /// Number of words loaded
+    pub length: usize,
+    /// Declared code region, if any (see `LC3VM::set_code_region`)
+    pub code_region: Option<(u16, u16)>,
+    /// Data region reported by the assembler, if loaded with `LC3VM::load_assembled`
+    pub data_region: Option<(u16, u16)>,
+    /// Known labels, sorted by address
+    pub symbols: Vec<(String, u16)>,
+}
+
+/// Every state change made by one step, as reported by `LC3VM::step_effects`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Effects {
+    /// Address the instruction ran from, and PC after the step
+    pub pc: (u16, u16),
+    /// General-purpose registers whose value changed, with the new value
+    pub register_writes: Vec<(Registers, u16)>,
+    /// Memory words stored by the instruction, with the stored value
+    pub memory_writes: Vec<(u16, u16)>,
+    /// COND before and after, if it changed
+    pub flags: Option<(u16, u16)>,
+    /// Data address accessed by a load or store
+    pub effective_address: Option<u16>,
+    /// Trap vector invoked, the only way this VM performs I/O
+    pub trap: Option<u16>,
+}
+
+/// Checkpoint of the machine state taken by `LC3VM::save_state`
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct VmSnapshot {
+    /// Register file indexed by `Registers` (PC and COND included)
+    pub registers: [u16; REG_COUNT],
+    /// Privilege and priority bits of the PSR
+    pub psr: u16,
+    /// Full memory image
+    pub memory: Vec<u16>,
+    pub running: bool,
+    pub instruction_count: u64,
+    /// Stack pointers parked while the other privilege mode owns R6
+    pub saved_ssp: u16,
+    pub saved_usp: u16,
+    /// Not serialized: a deserialized snapshot reads back as `NotStarted`
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub halt_status: HaltStatus,
+}
+
+/// Differences between two snapshots, from `VmSnapshot::diff`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct VmDiff {
+    /// Registers that changed as `(register, before, after)`, PC and COND included
+    pub registers: Vec<(Registers, u16, u16)>,
+    /// Memory words that changed as `(address, before, after)`
+    pub memory: Vec<(u16, u16, u16)>,
+    /// Halted before and after, if that changed
+    pub halted: Option<(bool, bool)>,
+}
+
+impl VmSnapshot {
+    /// What changed going from `self` to `other`
+    pub fn diff(&self, other: &VmSnapshot) -> VmDiff {
+        VmDiff {
+            registers: (0..=Registers::COND as u16)
+                .map(Registers::from)
+                .map(|reg| (reg, self.registers[reg as usize], other.registers[reg as usize]))
+                .filter(|&(_, before, after)| before != after)
+                .collect(),
+            memory: self
+                .memory
+                .iter()
+                .zip(&other.memory)
+                .enumerate()
+                .filter(|(_, (before, after))| before != after)
+                .map(|(address, (&before, &after))| (address as u16, before, after))
+                .collect(),
+            halted: (self.running != other.running).then_some((!self.running, !other.running)),
+        }
+    }
+}
+
+impl VmDiff {
+    pub fn is_empty(&self) -> bool {
+        self.registers.is_empty() && self.memory.is_empty() && self.halted.is_none()
+    }
+
+    /// One line per change, e.g. `R1: 0x0005 -> 0x0007` or `mem[x4000]: 0x0000 -> 0x0042`
+    pub fn report(&self) -> String {
+        let registers = self
+            .registers
+            .iter()
+            .map(|(reg, before, after)| format!("{}: 0x{:04X} -> 0x{:04X}", reg, before, after));
+        let memory = self.memory.iter().map(|(address, before, after)| {
+            format!("mem[x{:04X}]: 0x{:04X} -> 0x{:04X}", address, before, after)
+        });
+        let halted = self
+            .halted
+            .map(|(before, after)| format!("halted: {} -> {}", before, after));
+
+        registers.chain(memory).chain(halted).collect::<Vec<_>>().join("\n")
+    }
+}
+
+/// What one `LC3VM::step_detailed` call executed
+#[derive(Debug, PartialEq)]
+pub struct StepInfo {
+    /// Address the instruction was fetched from
+    pub pc: u16,
+    /// Raw instruction word
+    pub instruction: u16,
+    pub opcode: Opcodes,
+    /// Registers the instruction changed as `(register, old, new)`, PC included
+    pub register_changes: Vec<(Registers, u16, u16)>,
+    pub result: ExecutionResult,
+}
+
+/// What `LC3VM::step_back` needs to undo one step
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct JournalEntry {
+    /// Registers the step changed, with their previous values (PC and COND included)
+    registers: Vec<(Registers, u16)>,
+    /// Words the step overwrote, oldest first, as `(address, old value)`
+    memory: Vec<(u16, u16)>,
+    psr: u16,
+    /// Saved supervisor and user stack pointers
+    stacks: (u16, u16),
+}
+
+/// Notional energy per instruction, indexed by opcode value.
+/// Register-only ops cost 1, control flow 2, memory ops 4 (7 for the indirect forms), traps 10.
+pub const DEFAULT_ENERGY_COSTS: [u64; 16] = [
+    2,  // BR
+    1,  // ADD
+    4,  // LD
+    4,  // ST
+    2,  // JSR
+    1,  // AND
+    4,  // LDR
+    4,  // STR
+    2,  // RTI
+    1,  // NOT
+    7,  // LDI
+    7,  // STI
+    2,  // JMP
+    1,  // RES
+    1,  // LEA
+    10, // TRAP
+];
+
+/// Approximate clock cost of each instruction, used by `LC3VM::get_cycle_count`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CycleModel {
+    /// Cycles every instruction spends on fetch, decode and execute
+    pub base: u64,
+    /// Extra cycles per data memory access; LDI, STI and RTI make two, TRAP reads the vector table
+    pub memory_access: u64,
+}
+
+impl CycleModel {
+    /// Cycles `instruction` takes under this model
+    pub fn cost(&self, instruction: u16) -> u64 {
+        let accesses = match Opcodes::from_u16(extract_opcode(instruction)) {
+            Some(Opcodes::LD | Opcodes::LDR | Opcodes::ST | Opcodes::STR | Opcodes::TRAP) => 1,
+            Some(Opcodes::LDI | Opcodes::STI | Opcodes::RTI) => 2,
+            _ => 0,
+        };
+        self.base + accesses * self.memory_access
+    }
+}
+
+impl Default for CycleModel {
+    fn default() -> Self {
+        Self { base: 1, memory_access: 3 }
+    }
+}
+
+/// A single ALU operation applied to R0 by `LC3VM::eval`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AluOp {
+    /// R0 = R0 + value
+    Add(i16),
+    /// R0 = R0 & mask
+    And(u16),
+    /// R0 = !R0
+    Not,
+}
+
+/// Instrumentation callback run around every executed step
+pub type StepHook = Box<dyn FnMut(&LC3VM)>;
 
+/// Callback told about each standard trap (with its arguments in the registers) before it runs
+pub type TrapObserver = Box<dyn FnMut(TrapVectors, &RegisterFile)>;
+
+/// Handler for trap vectors the VM does not implement; gets the TRAP instruction
+pub type UnknownTrapHandler = fn(u16, &mut Memory, &mut RegisterFile) -> ExecutionResult;
 
-#[derive(Debug)]
 pub struct LC3VM {
     
     pub registers: RegisterFile,
@@ -14,6 +351,104 @@ pub struct LC3VM {
     pub running: bool,
    
     pub instruction_count: u64,
+
+    last_cc_setter: Option<u16>,
+
+    extended_traps: bool,
+
+    trap_table_dispatch: bool,
+
+    register_changes: Vec<(Registers, u16, u16)>,
+
+    res_policy: ResPolicy,
+
+    pre_hook: Option<StepHook>,
+
+    post_hook: Option<StepHook>,
+
+    symbols: HashMap<String, u16>,
+
+    /// Clock interrupt from `run_with_timer` as `(period, handler, instructions since it fired)`
+    timer: Option<(u64, u16, u64)>,
+
+    /// Breakpoint address to `(hits to skip, hits so far)`
+    breakpoints: HashMap<u16, (u32, u32)>,
+
+    watchpoints: HashSet<u16>,
+
+    strict: bool,
+
+    trap_observer: Option<TrapObserver>,
+
+    unknown_trap: Option<UnknownTrapHandler>,
+
+    trace_buffer: VecDeque<TraceRecord>,
+
+    trace_capacity: usize,
+
+    trace_sink: Option<Box<dyn Write>>,
+
+    journal: VecDeque<JournalEntry>,
+
+    journal_depth: usize,
+
+    code_region: Option<(u16, u16)>,
+
+    data_region: Option<(u16, u16)>,
+
+    warning_handler: Option<WarningHandler>,
+
+    stack_checks: bool,
+
+    unsaved_call: Option<u16>,
+
+    return_stack: Vec<u16>,
+
+    energy_costs: [u64; 16],
+
+    energy_used: u64,
+
+    cycle_model: CycleModel,
+
+    cycle_count: u64,
+
+    scheduled_writes: Vec<(u64, u16, u16)>,
+
+    legacy_trap_cc: bool,
+
+    halt_status: HaltStatus,
+
+    opcode_counts: [u64; 16],
+
+    entry_point: u16,
+
+    last_effective_address: Option<u16>,
+
+    last_store: Option<(u16, u16)>,
+
+    watch_hit: Option<(u16, u16, u16)>,
+
+    last_executed: Option<(u16, u16)>,
+
+    overwritten: Vec<(u16, u16)>,
+
+    program_length: usize,
+
+    max_string_length: usize,
+
+    write_log: Option<BTreeMap<u16, u16>>,
+
+    accessed: Option<BTreeSet<u16>>,
+
+    unique_instructions: Option<BTreeMap<u16, String>>,
+
+    io: Box<dyn Io>,
+
+    psr: u16,
+
+    saved_ssp: u16,
+
+    saved_usp: u16,
 }
 
 impl LC3VM {
@@ -24,11 +459,119 @@ impl LC3VM {
             memory: Memory::new(),
             running: false,
             instruction_count: 0,
+            last_cc_setter: None,
+            extended_traps: false,
+            trap_table_dispatch: false,
+            register_changes: Vec::new(),
+            res_policy: ResPolicy::default(),
+            pre_hook: None,
+            post_hook: None,
+            symbols: HashMap::new(),
+            timer: None,
+            breakpoints: HashMap::new(),
+            watchpoints: HashSet::new(),
+            strict: false,
+            trap_observer: None,
+            unknown_trap: None,
+            trace_buffer: VecDeque::new(),
+            trace_capacity: 0,
+            trace_sink: None,
+            journal: VecDeque::new(),
+            journal_depth: 0,
+            code_region: None,
+            data_region: None,
+            warning_handler: None,
+            stack_checks: false,
+            unsaved_call: None,
+            return_stack: Vec::new(),
+            energy_costs: DEFAULT_ENERGY_COSTS,
+            energy_used: 0,
+            cycle_model: CycleModel::default(),
+            cycle_count: 0,
+            scheduled_writes: Vec::new(),
+            legacy_trap_cc: false,
+            halt_status: HaltStatus::NotStarted,
+            opcode_counts: [0; 16],
+            entry_point: 0,
+            last_effective_address: None,
+            last_store: None,
+            watch_hit: None,
+            last_executed: None,
+            overwritten: Vec::new(),
+            program_length: 0,
+            max_string_length: DEFAULT_MAX_STRING_LENGTH,
+            write_log: None,
+            accessed: None,
+            unique_instructions: None,
+            io: Box::new(StdIo),
+            psr: 0,
+            saved_ssp: DEFAULT_SUPERVISOR_STACK,
+            saved_usp: 0,
+        }
+    }
+
+    /// A VM whose address space is limited to the first `words` addresses (see `Memory::with_size`)
+    pub fn with_memory_size(words: usize) -> Self {
+        Self {
+            memory: Memory::with_size(words),
+            ..Self::new()
+        }
+    }
+
+    /// Load `program` at `origin` into a fresh VM, feed it `input` and run it for at
+    /// most `max_steps` instructions. Only loading errors are returned as `Err`;
+    /// runtime errors end up in the report's `stop_reason`
+    pub fn execute(
+        program: &[u16],
+        origin: u16,
+        input: &[u8],
+        max_steps: u64,
+    ) -> Result<ExecutionReport, LC3Error> {
+        let mut vm = Self::new();
+        let io = BufferIo::new(input);
+        let output = io.output_handle();
+        vm.set_io(Box::new(io));
+        vm.initialize(origin, program)?;
+        let _ = vm.run_for(max_steps);
+
+        let output = output.borrow().clone();
+        Ok(ExecutionReport {
+            output,
+            registers: vm.registers.snapshot(),
+            stop_reason: vm.halt_status(),
+            instruction_count: vm.instruction_count,
+        })
+    }
+
+    /// Fill R0-R7 and all of memory with a reproducible xorshift pattern so
+    /// programs that rely on zero-initialized state misbehave visibly
+    pub fn new_randomized(seed: u64) -> Self {
+        let mut vm = Self::new();
+        // xorshift gets stuck at zero, so fold the seed into a non-zero constant
+        let mut state = seed ^ 0x9E37_79B9_7F4A_7C15;
+        let mut next = || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            (state >> 32) as u16
+        };
+
+        for i in 0..8 {
+            let _ = vm.registers.write(Registers::from(i), next());
         }
+        for address in 0..=u16::MAX {
+            let _ = vm.memory.write(address, next());
+        }
+
+        vm
     }
 
+    /// An empty program is rejected up front, leaving the VM untouched
     pub fn initialize(&mut self, start_address: u16, program: &[u16]) -> Result<(), String> {
-        
+        if program.is_empty() {
+            return Err("Cannot initialize with an empty program".to_string());
+        }
+
         self.registers.set_pc(start_address)
             .map_err(|_| "Failed to set program counter".to_string())?;
 
@@ -42,39 +585,201 @@ impl LC3VM {
 
         self.running = true;
         self.instruction_count = 0;
+        self.entry_point = start_address;
+        self.program_length = program.len();
+        self.last_cc_setter = None;
+        self.register_changes.clear();
+        self.trace_buffer.clear();
+        self.journal.clear();
+        self.energy_used = 0;
+        self.cycle_count = 0;
+        self.opcode_counts = [0; 16];
+        self.unsaved_call = None;
+        self.return_stack.clear();
+        self.clear_run_logs();
+        self.halt_status = HaltStatus::Running;
+
+        Ok(())
+    }
+
+    /// Parse whitespace-separated hex words (optionally `x`/`0x` prefixed),
+    /// then load them at `origin` and get ready to run like `initialize`
+    pub fn load_hex_words(&mut self, origin: u16, text: &str) -> Result<(), LC3Error> {
+        let words = text
+            .split_whitespace()
+            .enumerate()
+            .map(|(index, token)| {
+                let digits = token
+                    .strip_prefix("0x")
+                    .or_else(|| token.strip_prefix(['x', 'X']))
+                    .unwrap_or(token);
+                u16::from_str_radix(digits, 16).map_err(|_| {
+                    LC3Error::Custom(format!("Invalid hex word '{}' at token {}", token, index + 1))
+                })
+            })
+            .collect::<Result<Vec<u16>, LC3Error>>()?;
+
+        self.initialize(origin, &words).map_err(LC3Error::from)
+    }
 
+    /// Load an assembled program like `initialize` and take its code and data regions
+    /// and symbol table from the assembler (see `set_code_region` and `layout`)
+    pub fn load_assembled(&mut self, program: &AssembledProgram) -> Result<(), LC3Error> {
+        self.initialize(program.origin, &program.words)?;
+        self.code_region = program.code_region();
+        self.data_region = program.data_region();
+        self.symbols = program.symbols.clone().into_iter().collect();
         Ok(())
     }
 
+    /// Load an LC-3 `.obj` image at its own origin and get ready to run from there
+    pub fn load_object(&mut self, bytes: &[u8]) -> Result<(), LC3Error> {
+        let (origin, words) = parse_object(bytes)?;
+        self.initialize(origin, &words).map_err(LC3Error::from)
+    }
+
+    /// Read an `.obj` file from disk and load it like `load_object`
+    pub fn load_object_from_path(&mut self, path: &Path) -> Result<(), LC3Error> {
+        let bytes = std::fs::read(path)?;
+        self.load_object(&bytes)
+    }
+
   
     pub fn step(&mut self) -> Result<ExecutionResult, String> {
+        self.last_executed = None;
         if !self.running {
             return Ok(ExecutionResult::Halt);
         }
 
-      
-        let instruction = self.memory.fetch_instruction(&mut self.registers)
-            .ok_or("Failed to fetch instruction".to_string())?;
+        self.call_hook(|vm| &mut vm.pre_hook);
+        let outcome = self.execute_step();
+        self.halt_status = match &outcome {
+            Ok(ExecutionResult::Halt) => HaltStatus::HaltedCleanly,
+            Ok(_) => HaltStatus::Running,
+            Err(msg) => HaltStatus::Errored(LC3Error::Custom(msg.clone())),
+        };
+        self.call_hook(|vm| &mut vm.post_hook);
 
-       
-        let result = InstructionExecutor::execute_instruction(
-            instruction,
-            &mut self.memory,
-            &mut self.registers,
-        );
+        outcome
+    }
+
+    fn call_hook(&mut self, slot: fn(&mut Self) -> &mut Option<StepHook>) {
+        if let Some(mut hook) = slot(self).take() {
+            hook(self);
+            *slot(self) = Some(hook);
+        }
+    }
+
+    fn execute_step(&mut self) -> Result<ExecutionResult, String> {
+        self.last_store = None;
+        self.watch_hit = None;
+        self.overwritten.clear();
+        self.apply_scheduled_writes();
+        // Snapshot before interrupt entry so the journal and register changes cover it
+        let before = self.registers.snapshot();
+        let psr = self.psr;
+        let stacks = (self.saved_ssp, self.saved_usp);
+        if let ExecutionResult::Error(msg) = self.service_interrupts() {
+            self.running = false;
+            return Err(msg);
+        }
+        let pc = self.get_pc();
+        if self.strict && pc >= DEVICE_SPACE_START {
+            self.running = false;
+            return Err(format!("Instruction fetch from device space at 0x{:04X}", pc));
+        }
+        let Some(instruction) = self.memory.fetch_instruction(&mut self.registers) else {
+            self.running = false;
+            return Err("Failed to fetch instruction".to_string());
+        };
+        self.last_executed = Some((pc, instruction));
+
+        self.last_effective_address =
+            effective_address(instruction, self.get_pc(), &self.registers, &self.memory);
+        if let Some(accessed) = self.accessed.as_mut() {
+            accessed.insert(pc);
+            accessed.extend(self.last_effective_address);
+        }
+        if let Some(seen) = self.unique_instructions.as_mut() {
+            seen.entry(instruction).or_insert_with(|| disassemble(instruction, pc));
+        }
+        self.record_trace(pc, instruction);
+        self.observe_trap(instruction);
+        self.check_code_region_access(pc, instruction);
+        self.check_stack_usage(pc, instruction);
+        self.check_zero_base(pc, instruction);
+        if let Some(address) = self.last_effective_address.filter(|_| is_store(instruction)) {
+            self.note_overwrite(address);
+        }
+
+        // Take the stored value from the source register: reading it back from memory
+        // afterwards is wrong for device registers
+        let store = self
+            .last_effective_address
+            .filter(|_| is_store(instruction))
+            .map(|address| (address, self.register(Registers::from(extract_dr(instruction)))));
+        let watched_old = store
+            .filter(|(address, _)| self.watchpoints.contains(address))
+            .and_then(|(address, _)| self.memory.get_memory_slice(address as usize, 1).first())
+            .copied();
+
+        let result = match self
+            .check_access_control(pc)
+            .or_else(|| self.dispatch_trap_table(instruction))
+            .or_else(|| self.execute_vm_trap(instruction))
+            .or_else(|| self.execute_rti(instruction))
+            .or_else(|| self.execute_res(instruction))
+        {
+            Some(result) => result,
+            None => InstructionExecutor::execute_instruction(
+                instruction,
+                &mut self.memory,
+                &mut self.registers,
+                &mut *self.io,
+            ),
+        };
+
+        self.last_store = store.filter(|_| result == ExecutionResult::Continue);
+        self.watch_hit =
+            self.last_store.zip(watched_old).map(|((addr, new), old)| (addr, old, new));
+        self.forward_display_output();
+        if self.legacy_trap_cc && result == ExecutionResult::Continue {
+            self.update_cc_after_input_trap(instruction);
+        }
+
+        if let (Some(log), Some((address, value))) = (self.write_log.as_mut(), self.last_store) {
+            log.insert(address, value);
+        }
 
         self.instruction_count += 1;
+        if let Some((_, _, elapsed)) = self.timer.as_mut() {
+            *elapsed += 1;
+        }
+        self.apply_scheduled_writes();
+        self.opcode_counts[extract_opcode(instruction) as usize] += 1;
+        self.energy_used += self.energy_costs[extract_opcode(instruction) as usize];
+        self.cycle_count += self.cycle_model.cost(instruction);
+        self.record_register_changes(&before);
+        if result == ExecutionResult::Continue {
+            self.track_call(instruction);
+        }
+        self.record_journal(psr, stacks);
+        self.write_trace_line(pc, instruction);
+        if sets_condition_codes(instruction) && result == ExecutionResult::Continue {
+            self.last_cc_setter = Some(pc);
+        }
 
       
         match result {
             ExecutionResult::Halt => {
                 self.running = false;
+                self.flush_trace();
             }
             ExecutionResult::Error(ref msg) => {
                 self.running = false;
                 return Err(msg.clone());
             }
-            ExecutionResult::Continue => {
+            ExecutionResult::Continue | ExecutionResult::Exception => {
                
             }
         }
@@ -82,103 +787,2850 @@ impl LC3VM {
         Ok(result)
     }
 
-  
-    pub fn run(&mut self) -> Result<(), String> {
-        while self.running {
-            self.step()?;
+    /// Capture registers, memory and run state so they can be restored with `load_state`
+    pub fn save_state(&self) -> VmSnapshot {
+        VmSnapshot {
+            registers: self.registers.snapshot(),
+            psr: self.psr,
+            memory: self.memory.get_memory_slice(0, self.memory.size()).to_vec(),
+            running: self.running,
+            instruction_count: self.instruction_count,
+            saved_ssp: self.saved_ssp,
+            saved_usp: self.saved_usp,
+            halt_status: self.halt_status.clone(),
         }
-        Ok(())
     }
 
-  
-    pub fn run_for(&mut self, max_instructions: u64) -> Result<(), String> {
-        let start_count = self.instruction_count;
-        
-        while self.running && (self.instruction_count - start_count) < max_instructions {
-            self.step()?;
+    /// Rewind to a checkpoint from `save_state`; hooks, breakpoints and other settings are kept
+    pub fn load_state(&mut self, snapshot: &VmSnapshot) {
+        for reg in (0..=Registers::COND as u16).map(Registers::from) {
+            let _ = self.registers.write(reg, snapshot.registers[reg as usize]);
         }
-        
-        Ok(())
+        self.psr = snapshot.psr;
+        self.memory.restore(&snapshot.memory);
+        self.running = snapshot.running;
+        self.instruction_count = snapshot.instruction_count;
+        self.saved_ssp = snapshot.saved_ssp;
+        self.saved_usp = snapshot.saved_usp;
+        self.halt_status = if self.running {
+            HaltStatus::Running
+        } else {
+            snapshot.halt_status.clone()
+        };
     }
 
-  
-    pub fn get_pc(&self) -> u16 {
-        self.registers.get_pc()
+    /// Step once and describe everything the instruction changed
+    pub fn step_effects(&mut self) -> Result<Effects, LC3Error> {
+        let pc = self.get_pc();
+
+        self.step()?;
+        let (pc, instruction) = self.last_executed.unwrap_or((pc, self.peek(pc)));
+
+        let mut effects = Effects {
+            pc: (pc, self.get_pc()),
+            register_writes: Vec::new(),
+            memory_writes: Vec::new(),
+            flags: None,
+            effective_address: self.last_effective_address,
+            trap: None,
+        };
+        for &(reg, old, new) in &self.register_changes {
+            match reg {
+                Registers::PC => {}
+                Registers::COND => effects.flags = Some((old, new)),
+                _ => effects.register_writes.push((reg, new)),
+            }
+        }
+        effects.memory_writes.extend(self.last_store);
+        if Opcodes::from_u16(extract_opcode(instruction)) == Some(Opcodes::TRAP) {
+            effects.trap = Some(extract_trap_vector(instruction));
+        }
+
+        Ok(effects)
     }
 
-   
-    pub fn get_register(&self, reg: Registers) -> Option<u16> {
-        self.registers.read(reg)
+    /// Step once and report what ran, without re-reading memory or re-decoding
+    pub fn step_detailed(&mut self) -> Result<StepInfo, String> {
+        let pc = self.get_pc();
+        let count = self.instruction_count;
+
+        let result = self.step()?;
+        let (pc, instruction) = self.last_executed.unwrap_or((pc, self.peek(pc)));
+
+        let register_changes = if self.instruction_count == count {
+            Vec::new()
+        } else {
+            self.register_changes.clone()
+        };
+        Ok(StepInfo {
+            pc,
+            instruction,
+            opcode: Opcodes::from_u16(extract_opcode(instruction)).unwrap_or(Opcodes::RES),
+            register_changes,
+            result,
+        })
     }
 
-   
-    pub fn set_register(&mut self, reg: Registers, value: u16) -> Result<(), String> {
-        self.registers.write(reg, value)
-            .map_err(|e| format!("Failed to write to register: {}", e))
+    /// Undo the most recent journaled step (see `enable_journal`), including any
+    /// interrupt entry. Memory written by trap routines such as GETS is not restored
+    pub fn step_back(&mut self) -> Result<(), String> {
+        let entry = self
+            .journal
+            .pop_back()
+            .ok_or("No journaled step to undo".to_string())?;
+
+        for (reg, old) in entry.registers {
+            self.registers.write(reg, old).map_err(|e| e.to_string())?;
+        }
+        for &(address, old) in entry.memory.iter().rev() {
+            self.memory.poke(address, old).map_err(|e| e.to_string())?;
+        }
+        self.psr = entry.psr;
+        (self.saved_ssp, self.saved_usp) = entry.stacks;
+        self.instruction_count = self.instruction_count.saturating_sub(1);
+        self.register_changes.clear();
+        self.running = true;
+        self.halt_status = HaltStatus::Running;
+        Ok(())
     }
 
-    
-    pub fn read_memory(&self, address: u16) -> Option<u16> {
-        self.memory.read(address)
+    fn record_register_changes(&mut self, before: &[u16]) {
+        let after = self.registers.snapshot();
+        self.register_changes = (0..=Registers::COND as u16)
+            .map(Registers::from)
+            .filter(|&reg| before[reg as usize] != after[reg as usize])
+            .map(|reg| (reg, before[reg as usize], after[reg as usize]))
+            .collect();
     }
 
-  
-    pub fn write_memory(&mut self, address: u16, value: u16) -> Result<(), String> {
-        self.memory.write(address, value)
-            .map_err(|_| "Failed to write to memory".to_string())
+    /// Remember the word at `address` before the step overwrites it, for `step_back`
+    fn note_overwrite(&mut self, address: u16) {
+        if self.journal_depth == 0 {
+            return;
+        }
+        if let Some(&old) = self.memory.get_memory_slice(address as usize, 1).first() {
+            self.overwritten.push((address, old));
+        }
     }
 
-   
-    pub fn get_instruction_count(&self) -> u64 {
-        self.instruction_count
+    fn record_journal(&mut self, psr: u16, stacks: (u16, u16)) {
+        if self.journal_depth == 0 {
+            return;
+        }
+        if self.journal.len() == self.journal_depth {
+            self.journal.pop_front();
+        }
+        self.journal.push_back(JournalEntry {
+            registers: self.register_changes.iter().map(|&(reg, old, _)| (reg, old)).collect(),
+            memory: std::mem::take(&mut self.overwritten),
+            psr,
+            stacks,
+        });
     }
 
- 
-    pub fn is_running(&self) -> bool {
-        self.running
+    fn record_trace(&mut self, pc: u16, instruction: u16) {
+        if self.trace_capacity == 0 {
+            return;
+        }
+        if self.trace_buffer.len() == self.trace_capacity {
+            self.trace_buffer.pop_front();
+        }
+        self.trace_buffer.push_back(TraceRecord {
+            step: self.instruction_count,
+            pc,
+            instruction,
+        });
     }
 
-   
-    pub fn halt(&mut self) {
-        self.running = false;
+    fn write_trace_line(&mut self, pc: u16, instruction: u16) {
+        let Some(sink) = self.trace_sink.as_mut() else { return };
+
+        let disassembly = disassemble(instruction, pc);
+        let mut line = format!("x{:04X}  x{:04X}  {}", pc, instruction, disassembly);
+        let changes: Vec<String> = self
+            .register_changes
+            .iter()
+            .filter(|&&(reg, _, _)| reg != Registers::PC)
+            .map(|&(reg, _, new)| format!("{:?}=x{:04X}", reg, new))
+            .collect();
+        if !changes.is_empty() {
+            line.push_str("  ; ");
+            line.push_str(&changes.join(" "));
+        }
+        let _ = writeln!(sink, "{}", line);
     }
 
-   
-    pub fn reset(&mut self) {
-        self.registers = RegisterFile::new();
-        self.memory = Memory::new();
-        self.running = false;
-        self.instruction_count = 0;
+    fn flush_trace(&mut self) {
+        if let Some(sink) = self.trace_sink.as_mut() {
+            let _ = sink.flush();
+        }
     }
 
-   
-    pub fn debug_info(&self) -> String {
-        format!(
-            "LC-3 VM State:\n\
-            PC: 0x{:04X}\n\
-            R0: 0x{:04X}  R1: 0x{:04X}  R2: 0x{:04X}  R3: 0x{:04X}\n\
-            R4: 0x{:04X}  R5: 0x{:04X}  R6: 0x{:04X}  R7: 0x{:04X}\n\
-            COND: 0x{:04X}\n\
-            Instructions executed: {}\n\
-            Running: {}",
-            self.get_pc(),
-            self.get_register(Registers::R0).unwrap_or(0),
-            self.get_register(Registers::R1).unwrap_or(0),
-            self.get_register(Registers::R2).unwrap_or(0),
-            self.get_register(Registers::R3).unwrap_or(0),
-            self.get_register(Registers::R4).unwrap_or(0),
-            self.get_register(Registers::R5).unwrap_or(0),
-            self.get_register(Registers::R6).unwrap_or(0),
-            self.get_register(Registers::R7).unwrap_or(0),
-            self.get_register(Registers::COND).unwrap_or(0),
-            self.instruction_count,
-            self.running
-        )
+    fn update_cc_after_input_trap(&mut self, instruction: u16) {
+        if Opcodes::from_u16(extract_opcode(instruction)) != Some(Opcodes::TRAP) {
+            return;
+        }
+
+        if matches!(
+            TrapVectors::from_u16(extract_trap_vector(instruction)),
+            Some(TrapVectors::GETC) | Some(TrapVectors::IN)
+        ) {
+            let _ = self.registers.update_condition_code(self.register(Registers::R0));
+        }
     }
-}
+
+    fn apply_scheduled_writes(&mut self) {
+        let count = self.instruction_count;
+        let memory = &mut self.memory;
+        self.scheduled_writes.retain(|&(at, address, value)| {
+            if at > count {
+                return true;
+            }
+            let _ = memory.write(address, value);
+            false
+        });
+    }
+
+    fn forward_display_output(&mut self) {
+        let Some(display) = self.memory.display_mut() else { return };
+
+        let output = display.take_output();
+        if !output.is_empty() {
+            output.into_iter().for_each(|byte| self.io.write_byte(byte));
+            self.io.flush();
+        }
+    }
+
+    fn warn(&mut self, warning: Warning) {
+        if let Some(handler) = self.warning_handler.as_mut() {
+            handler(&warning);
+        }
+    }
+
+    fn check_code_region_access(&mut self, pc: u16, instruction: u16) {
+        let Some((start, end)) = self.code_region else { return };
+
+        let in_region = |address: &u16| (start..=end).contains(address);
+        if let Some(address) = self.last_effective_address.filter(in_region) {
+            self.warn(Warning::CodeRegionAccess {
+                pc,
+                address,
+                store: is_store(instruction),
+            });
+        }
+    }
+
+    fn check_zero_base(&mut self, pc: u16, instruction: u16) {
+        if !self.strict
+            || !matches!(
+                Opcodes::from_u16(extract_opcode(instruction)),
+                Some(Opcodes::LDR | Opcodes::STR)
+            )
+        {
+            return;
+        }
+
+        let Ok(base_reg) = Registers::try_from_field(extract_sr1(instruction)) else { return };
+        let base = self.register(base_reg);
+        let offset = sign_extend_offset6(extract_offset6(instruction)) as i16;
+        if let Some(address) = self.last_effective_address.filter(|_| base == 0 && offset < 0) {
+            self.warn(Warning::ZeroBaseNegativeOffset { pc, address });
+        }
+    }
+
+    fn check_stack_usage(&mut self, pc: u16, instruction: u16) {
+        if !self.stack_checks {
+            return;
+        }
+
+        let r6 = Registers::R6 as u16;
+        let r7 = Registers::R7 as u16;
+        match Opcodes::from_u16(extract_opcode(instruction)) {
+            Some(opcode @ (Opcodes::LDR | Opcodes::STR)) if extract_sr1(instruction) == r6 => {
+                let offset = sign_extend_offset6(extract_offset6(instruction)) as i16;
+                if offset > 0 {
+                    self.warn(Warning::StackAboveTop {
+                        pc,
+                        offset,
+                        store: opcode == Opcodes::STR,
+                    });
+                }
+                if opcode == Opcodes::STR && extract_dr(instruction) == r7 {
+                    self.unsaved_call = None;
+                }
+            }
+            Some(Opcodes::ST | Opcodes::STR | Opcodes::STI) if extract_dr(instruction) == r7 => {
+                self.unsaved_call = None;
+            }
+            Some(Opcodes::JMP) if extract_sr1(instruction) == r7 => {
+                self.unsaved_call = None;
+            }
+            Some(Opcodes::JSR) => {
+                if let Some(call) = self.unsaved_call {
+                    self.warn(Warning::LostReturnAddress { pc, call });
+                }
+                self.unsaved_call = Some(pc);
+            }
+            _ => {}
+        }
+    }
+
+    fn track_call(&mut self, instruction: u16) {
+        match Opcodes::from_u16(extract_opcode(instruction)) {
+            Some(Opcodes::JSR) => self.return_stack.push(self.register(Registers::R7)),
+            Some(Opcodes::JMP) if extract_sr1(instruction) == 7 => {
+                self.return_stack.pop();
+            }
+            _ => {}
+        }
+    }
+
+    fn observe_trap(&mut self, instruction: u16) {
+        if Opcodes::from_u16(extract_opcode(instruction)) != Some(Opcodes::TRAP) {
+            return;
+        }
+
+        if let (Some(observer), Some(vector)) = (
+            self.trap_observer.as_mut(),
+            TrapVectors::from_u16(extract_trap_vector(instruction)),
+        ) {
+            observer(vector, &self.registers);
+        }
+    }
+
+    /// In user mode, an instruction fetched from, or loading or storing, outside user
+    /// space raises an access control violation instead of running
+    fn check_access_control(&mut self, pc: u16) -> Option<ExecutionResult> {
+        if !self.is_user_mode() {
+            return None;
+        }
+        let user_space = PC_START..DEVICE_SPACE_START;
+        if user_space.contains(&pc)
+            && self.last_effective_address.is_none_or(|address| user_space.contains(&address))
+        {
+            return None;
+        }
+        Some(self.raise_exception(ACCESS_CONTROL_VIOLATION))
+    }
+
+    fn execute_rti(&mut self, instruction: u16) -> Option<ExecutionResult> {
+        if Opcodes::from_u16(extract_opcode(instruction)) != Some(Opcodes::RTI) {
+            return None;
+        }
+        if self.is_user_mode() {
+            return Some(self.raise_exception(PRIVILEGE_MODE_EXCEPTION));
+        }
+
+        let sp = self.register(Registers::R6);
+        let (Some(pc), Some(psr)) = (self.memory.read(sp), self.memory.read(sp.wrapping_add(1)))
+        else {
+            return Some(ExecutionResult::Error(format!(
+                "RTI: supervisor stack at 0x{:04X} is outside memory",
+                sp
+            )));
+        };
+        let _ = self.registers.write(Registers::R6, sp.wrapping_add(2));
+        let _ = self.registers.set_pc(pc);
+        self.set_psr(psr);
+
+        Some(ExecutionResult::Continue)
+    }
+
+    /// Dispatch a pending device interrupt before the next fetch, if the
+    /// running code's priority is low enough to allow it
+    fn service_interrupts(&mut self) -> ExecutionResult {
+        let priority = (self.psr & PSR_PRIORITY_MASK) >> 8;
+        if let Some((period, handler, elapsed)) = self.timer.as_mut()
+            && *elapsed >= *period
+            && priority < TIMER_PRIORITY
+        {
+            *elapsed = 0;
+            let handler = *handler;
+            if let Err(e) = self.enter_supervisor(Some(TIMER_PRIORITY)) {
+                return ExecutionResult::Error(format!("Timer interrupt: {}", e));
+            }
+            let _ = self.registers.set_pc(handler);
+            return ExecutionResult::Continue;
+        }
+        let pending = self
+            .memory
+            .keyboard_mut()
+            .is_some_and(|keyboard| keyboard.interrupt_pending());
+        if pending && priority < KEYBOARD_PRIORITY {
+            return self.raise_interrupt(KEYBOARD_INTERRUPT, Some(KEYBOARD_PRIORITY));
+        }
+        ExecutionResult::Continue
+    }
+
+    /// Enter the handler for interrupt/exception `vector`: switch to supervisor mode,
+    /// optionally raise the priority, and jump through the interrupt vector table
+    fn raise_interrupt(&mut self, vector: u16, priority: Option<u16>) -> ExecutionResult {
+        let entered = self.enter_supervisor(priority);
+        let handler = self.memory.read(INTERRUPT_VECTOR_TABLE.wrapping_add(vector));
+        let (Ok(()), Some(handler)) = (entered, handler) else {
+            return ExecutionResult::Error(format!(
+                "Interrupt 0x{:02X}: supervisor stack or vector table outside memory",
+                vector
+            ));
+        };
+        let _ = self.registers.set_pc(handler);
+
+        ExecutionResult::Continue
+    }
+
+    /// Like `raise_interrupt`, but for an exception caused by the instruction being
+    /// executed, which therefore never runs
+    fn raise_exception(&mut self, vector: u16) -> ExecutionResult {
+        match self.raise_interrupt(vector, None) {
+            ExecutionResult::Continue => ExecutionResult::Exception,
+            result => result,
+        }
+    }
+
+    /// Switch to the supervisor stack if needed and push PSR then PC, leaving
+    /// the machine in supervisor mode; RTI undoes it
+    fn enter_supervisor(&mut self, priority: Option<u16>) -> Result<(), LC3Error> {
+        let old_psr = self.psr();
+        if self.psr & PSR_USER_MODE != 0 {
+            self.saved_usp = self.register(Registers::R6);
+            let _ = self.registers.write(Registers::R6, self.saved_ssp);
+            self.psr &= !PSR_USER_MODE;
+        }
+        if let Some(priority) = priority {
+            self.psr = (self.psr & !PSR_PRIORITY_MASK) | ((priority << 8) & PSR_PRIORITY_MASK);
+        }
+
+        let sp = self.register(Registers::R6);
+        self.note_overwrite(sp.wrapping_sub(1));
+        self.note_overwrite(sp.wrapping_sub(2));
+        self.memory.write(sp.wrapping_sub(1), old_psr)?;
+        self.memory.write(sp.wrapping_sub(2), self.get_pc())?;
+        let _ = self.registers.write(Registers::R6, sp.wrapping_sub(2));
+        Ok(())
+    }
+
+    fn execute_res(&mut self, instruction: u16) -> Option<ExecutionResult> {
+        if Opcodes::from_u16(extract_opcode(instruction)) != Some(Opcodes::RES) {
+            return None;
+        }
+
+        match self.res_policy {
+            ResPolicy::Error => None,
+            ResPolicy::Nop => Some(ExecutionResult::Continue),
+            ResPolicy::Custom(handler) => {
+                Some(handler(instruction, &mut self.memory, &mut self.registers))
+            }
+        }
+    }
+
+    // Traps that depend on VM settings are run here instead of in InstructionExecutor
+    fn dispatch_trap_table(&mut self, instruction: u16) -> Option<ExecutionResult> {
+        if !self.trap_table_dispatch
+            || Opcodes::from_u16(extract_opcode(instruction)) != Some(Opcodes::TRAP)
+        {
+            return None;
+        }
+
+        let vector = extract_trap_vector(instruction);
+        let Some(routine) = self.memory.read(vector) else {
+            return Some(ExecutionResult::Error(format!(
+                "Trap vector 0x{:02X} is outside memory",
+                vector
+            )));
+        };
+        if routine == 0 {
+            return Some(ExecutionResult::Error(format!(
+                "no handler installed for trap x{:02X}",
+                vector
+            )));
+        }
+        // Same convention from either mode, so one routine serves both: PSR and PC go on
+        // the supervisor stack, the routine runs in supervisor mode and returns with RTI
+        if self.enter_supervisor(None).is_err() {
+            return Some(ExecutionResult::Error(format!(
+                "Trap 0x{:02X}: supervisor stack outside memory",
+                vector
+            )));
+        }
+        let _ = self.registers.set_pc(routine);
+        Some(ExecutionResult::Continue)
+    }
+
+    fn execute_vm_trap(&mut self, instruction: u16) -> Option<ExecutionResult> {
+        if Opcodes::from_u16(extract_opcode(instruction)) != Some(Opcodes::TRAP) {
+            return None;
+        }
+
+        match extract_trap_vector(instruction) {
+            vector if vector == TrapVectors::PUTS.to_u16() => {
+                Some(InstructionExecutor::execute_puts(
+                    &self.memory,
+                    &self.registers,
+                    &mut *self.io,
+                    self.max_string_length,
+                ))
+            }
+            vector if vector == TrapVectors::PUTSP.to_u16() => {
+                Some(InstructionExecutor::execute_putsp(
+                    &self.memory,
+                    &self.registers,
+                    &mut *self.io,
+                    self.max_string_length,
+                ))
+            }
+            TRAP_GETS if self.extended_traps => Some(InstructionExecutor::execute_gets(
+                &mut self.memory,
+                &mut self.registers,
+                &mut *self.io,
+            )),
+            TRAP_PUTHEX if self.extended_traps => {
+                Some(InstructionExecutor::execute_puthex(&self.registers, &mut *self.io))
+            }
+            vector => match (TrapVectors::from_u16(vector), self.unknown_trap) {
+                (None, Some(handler)) => {
+                    Some(handler(instruction, &mut self.memory, &mut self.registers))
+                }
+                _ => None,
+            },
+        }
+    }
+
+  
+    pub fn run(&mut self) -> Result<(), String> {
+        while self.running {
+            self.step()?;
+        }
+        Ok(())
+    }
+
+    pub fn run_with_stats(&mut self) -> Result<RunStats, LC3Error> {
+        let mut stats = RunStats::default();
+        let mut depth = 0usize;
+
+        while self.running {
+            // Interrupt entry leaves COND alone, so it is the flags the instruction sees
+            let cond = self.registers.get_condition_code();
+
+            self.step()?;
+            let Some((_, instruction)) = self.last_executed else { break };
+            let opcode = Opcodes::from_u16(extract_opcode(instruction));
+            let branch_taken = (instruction >> 9) & cond != 0;
+
+            stats.instructions_executed += 1;
+            let Some(opcode) = opcode else { continue };
+            stats.opcode_counts[opcode.to_u16() as usize] += 1;
+
+            match opcode {
+                Opcodes::BR => {
+                    if branch_taken {
+                        stats.branches_taken += 1;
+                    } else {
+                        stats.branches_not_taken += 1;
+                    }
+                }
+                Opcodes::LD | Opcodes::LDR => stats.memory_reads += 1,
+                Opcodes::LDI => stats.memory_reads += 2,
+                Opcodes::ST | Opcodes::STR => stats.memory_writes += 1,
+                Opcodes::STI => {
+                    stats.memory_reads += 1;
+                    stats.memory_writes += 1;
+                }
+                Opcodes::JSR => {
+                    depth += 1;
+                    stats.max_stack_depth = stats.max_stack_depth.max(depth);
+                }
+                Opcodes::JMP if extract_sr1(instruction) == 7 => {
+                    depth = depth.saturating_sub(1);
+                }
+                _ => {}
+            }
+        }
+
+        Ok(stats)
+    }
+
+  
+    pub fn run_for(&mut self, max_instructions: u64) -> Result<(), String> {
+        let start_count = self.instruction_count;
+        
+        while self.running && (self.instruction_count - start_count) < max_instructions {
+            self.step()?;
+        }
+        if self.running {
+            self.halt_status = HaltStatus::StepLimitReached;
+        }
+        
+        Ok(())
+    }
+
+    pub fn add_breakpoint(&mut self, address: u16) {
+        self.add_breakpoint_count(address, 0);
+    }
+
+    /// Breakpoint that lets the first `skip` arrivals at `address` run on
+    pub fn add_breakpoint_count(&mut self, address: u16, skip: u32) {
+        self.breakpoints.insert(address, (skip, 0));
+    }
+
+    /// Times PC has arrived at the breakpoint at `address`, fired or not
+    pub fn breakpoint_hits(&self, address: u16) -> Option<u32> {
+        self.breakpoints.get(&address).map(|&(_, hits)| hits)
+    }
+
+    /// Returns whether a breakpoint was set at `address`
+    pub fn remove_breakpoint(&mut self, address: u16) -> bool {
+        self.breakpoints.remove(&address).is_some()
+    }
+
+    /// Stop `run_until_breakpoint` after any store to `address`
+    pub fn add_watchpoint(&mut self, address: u16) {
+        self.watchpoints.insert(address);
+    }
+
+    /// Returns whether a watchpoint was set at `address`
+    pub fn remove_watchpoint(&mut self, address: u16) -> bool {
+        self.watchpoints.remove(&address)
+    }
+
+    /// Run until PC lands on a breakpoint (checked before each fetch), a store hits a
+    /// watchpoint, the program halts, or `BREAKPOINT_STEP_LIMIT` steps pass. The first
+    /// instruction always runs, so calling this again resumes from the last stop
+    pub fn run_until_breakpoint(&mut self) -> Result<BreakReason, String> {
+        for _ in 0..BREAKPOINT_STEP_LIMIT {
+            if !self.running {
+                return Ok(BreakReason::Halted);
+            }
+            self.step()?;
+            if let Some((addr, old, new)) = self.watch_hit {
+                return Ok(BreakReason::Watchpoint { addr, old, new });
+            }
+            let pc = self.get_pc();
+            if let Some((skip, hits)) = self.breakpoints.get_mut(&pc).filter(|_| self.running) {
+                *hits += 1;
+                if *hits > *skip {
+                    return Ok(BreakReason::Breakpoint(pc));
+                }
+            }
+        }
+
+        if self.running {
+            Ok(BreakReason::MaxSteps)
+        } else {
+            Ok(BreakReason::Halted)
+        }
+    }
+
+    /// Step until `reg` holds `value`, checked after each instruction
+    pub fn run_until_register(&mut self, reg: Registers, value: u16, max: u64) -> RunOutcome {
+        for _ in 0..max {
+            if !self.running {
+                return RunOutcome::Halted;
+            }
+            if let Err(msg) = self.step() {
+                return RunOutcome::Error(msg);
+            }
+            if self.get_register(reg) == Some(value) {
+                return RunOutcome::ConditionMet;
+            }
+        }
+
+        if self.running {
+            self.halt_status = HaltStatus::StepLimitReached;
+            RunOutcome::StepLimitReached
+        } else {
+            RunOutcome::Halted
+        }
+    }
+
+    /// Run at most `max` instructions with a clock interrupt entering `handler` every
+    /// `period` instructions, for preemption demos. The handler runs in supervisor
+    /// mode at `TIMER_PRIORITY` and returns with RTI; the timer is removed afterwards
+    pub fn run_with_timer(&mut self, period: u64, handler: u16, max: u64) -> RunOutcome {
+        self.timer = Some((period, handler, 0));
+        let mut outcome = RunOutcome::StepLimitReached;
+        for _ in 0..max {
+            if !self.running {
+                outcome = RunOutcome::Halted;
+                break;
+            }
+            if let Err(msg) = self.step() {
+                outcome = RunOutcome::Error(msg);
+                break;
+            }
+        }
+        self.timer = None;
+
+        match outcome {
+            RunOutcome::StepLimitReached if !self.running => RunOutcome::Halted,
+            RunOutcome::StepLimitReached => {
+                self.halt_status = HaltStatus::StepLimitReached;
+                outcome
+            }
+            _ => outcome,
+        }
+    }
+
+    /// Run whole instructions while they fit in `max_cycles` under the cycle model.
+    /// An instruction that would take the run past the budget is not started, and
+    /// the run ends with `StepLimitReached`
+    pub fn run_cycles(&mut self, max_cycles: u64) -> RunOutcome {
+        let start = self.cycle_count;
+
+        while self.running {
+            let next = self.cycle_model.cost(self.peek(self.get_pc()));
+            if self.cycle_count - start + next > max_cycles {
+                self.halt_status = HaltStatus::StepLimitReached;
+                return RunOutcome::StepLimitReached;
+            }
+            if let Err(msg) = self.step() {
+                return RunOutcome::Error(msg);
+            }
+        }
+
+        RunOutcome::Halted
+    }
+
+    /// Step until a store executes and return the `(address, value)` it wrote,
+    /// or `None` if the program halts or `max` steps pass without one
+    pub fn run_to_next_store(&mut self, max: u64) -> Result<Option<(u16, u16)>, LC3Error> {
+        for _ in 0..max {
+            if !self.running {
+                return Ok(None);
+            }
+            self.step()?;
+            if let Some(write) = self.last_store {
+                return Ok(Some(write));
+            }
+        }
+
+        if self.running {
+            self.halt_status = HaltStatus::StepLimitReached;
+        }
+        Ok(None)
+    }
+
+    /// Run at most `max_steps` instructions, stopping early on a self-jump or a
+    /// run of zero words (execution fell off the end of the program)
+    pub fn run_safely(&mut self, max_steps: u64) -> SafeRunOutcome {
+        let mut zero_run = 0;
+        let mut zero_start = 0;
+
+        for _ in 0..max_steps {
+            if !self.running {
+                return SafeRunOutcome::Halted;
+            }
+            if let Err(msg) = self.step() {
+                return SafeRunOutcome::Error(msg);
+            }
+            let Some((pc, instruction)) = self.last_executed else { continue };
+            if self.running && self.get_pc() == pc {
+                return SafeRunOutcome::SelfLoop { pc };
+            }
+            if instruction != 0 {
+                zero_run = 0;
+                continue;
+            }
+            if zero_run == 0 {
+                zero_start = pc;
+            }
+            zero_run += 1;
+            if zero_run >= ZERO_RUN_LIMIT {
+                return SafeRunOutcome::ZeroRun { start: zero_start };
+            }
+        }
+
+        if self.running {
+            self.halt_status = HaltStatus::StepLimitReached;
+            SafeRunOutcome::StepLimitReached
+        } else {
+            SafeRunOutcome::Halted
+        }
+    }
+
+    /// Record a label for the loaded program (e.g. from an assembler symbol table)
+    pub fn add_symbol(&mut self, name: &str, address: u16) {
+        self.symbols.insert(name.to_string(), address);
+    }
+
+    pub fn symbol_address(&self, name: &str) -> Option<u16> {
+        self.symbols.get(name).copied()
+    }
+
+    /// Move the PC to a known label so the next fetch happens there
+    pub fn goto_label(&mut self, name: &str) -> Result<(), LC3Error> {
+        let address = self
+            .symbol_address(name)
+            .ok_or_else(|| LC3Error::Custom(format!("Unknown label: {}", name)))?;
+        self.registers.set_pc(address)
+    }
+
+    /// Run to HALT within `max_steps` and return R0, panicking with the VM state otherwise
+    #[cfg(feature = "testing")]
+    pub fn expect_halt(&mut self, max_steps: u64) -> u16 {
+        if let Err(msg) = self.run_for(max_steps) {
+            panic!("program errored at PC 0x{:04X}: {}\n{}", self.get_pc(), msg, self.debug_info());
+        }
+        if self.running {
+            panic!("program did not halt within {} steps\n{}", max_steps, self.debug_info());
+        }
+        self.register(Registers::R0)
+    }
+
+    /// Best-effort backtrace, innermost call first.
+    ///
+    /// LC-3 has no enforced calling convention, so this assumes the common one:
+    /// R6 is a downward-growing stack pointer and callers push R7 before nested JSRs.
+    /// Every word from R6 up to (not including) `stack_base` is treated as a candidate,
+    /// and kept only if the word before it is a JSR/JSRR, i.e. it looks like a return address.
+    pub fn call_stack(&self, stack_base: u16) -> Vec<u16> {
+        let top = self.register(Registers::R6);
+
+        (top..stack_base)
+            .filter_map(|address| self.memory.peek(address))
+            .filter(|&saved| {
+                let call = self.peek(saved.wrapping_sub(1));
+                Opcodes::from_u16(extract_opcode(call)) == Some(Opcodes::JSR)
+            })
+            .collect()
+    }
+
+    /// Return addresses of the subroutines currently active, innermost last.
+    /// JSR/JSRR push the saved R7 and RET pops, so this works without a stack in memory
+    pub fn return_stack(&self) -> &[u16] {
+        &self.return_stack
+    }
+
+    /// Execute one instruction word directly, without fetching it from memory
+    pub fn execute_raw(&mut self, instruction: u16) -> ExecutionResult {
+        InstructionExecutor::execute_instruction(
+            instruction,
+            &mut self.memory,
+            &mut self.registers,
+            &mut *self.io,
+        )
+    }
+
+    /// Start from R0 = 0, apply each op as a real ADD/AND/NOT and return R0.
+    /// Operands outside the imm5 range go through R1, which is clobbered.
+    pub fn eval(&mut self, ops: &[AluOp]) -> u16 {
+        let _ = self.registers.write(Registers::R0, 0);
+
+        for &op in ops {
+            let instruction = match op {
+                AluOp::Add(value) => self.alu_operand(Opcodes::ADD, value as u16),
+                AluOp::And(mask) => self.alu_operand(Opcodes::AND, mask),
+                AluOp::Not => (Opcodes::NOT.to_u16() << 12) | 0x3F,
+            };
+            self.execute_raw(instruction);
+        }
+
+        self.register(Registers::R0)
+    }
+
+    fn alu_operand(&mut self, opcode: Opcodes, value: u16) -> u16 {
+        let base = opcode.to_u16() << 12;
+        if (-16..=15).contains(&(value as i16)) {
+            base | 0x20 | (value & 0x1F)
+        } else {
+            let _ = self.registers.write(Registers::R1, value);
+            base | 0x1
+        }
+    }
+
+    /// Origin the current program was loaded at
+    pub fn entry_point(&self) -> u16 {
+        self.entry_point
+    }
+
+    pub fn layout(&self) -> ProgramLayout {
+        let mut symbols: Vec<(String, u16)> = self
+            .symbols
+            .iter()
+            .map(|(name, &address)| (name.clone(), address))
+            .collect();
+        symbols.sort_by(|a, b| a.1.cmp(&b.1).then_with(|| a.0.cmp(&b.0)));
+
+        ProgramLayout {
+            origin: self.entry_point,
+            length: self.program_length,
+            code_region: self.code_region,
+            data_region: self.data_region,
+            symbols,
+        }
+    }
+
+    /// Whether PC still points at the entry point, e.g. before the first step
+    pub fn verify_pc_at_entry(&self) -> bool {
+        self.get_pc() == self.entry_point
+    }
+
+  
+    pub fn get_pc(&self) -> u16 {
+        self.registers.get_pc()
+    }
+
+   
+    pub fn get_register(&self, reg: Registers) -> Option<u16> {
+        self.registers.read(reg)
+    }
+
+    /// Infallible register read for the common case of a known register
+    pub fn register(&self, reg: Registers) -> u16 {
+        self.registers.read(reg).unwrap_or(0)
+    }
+
+   
+    pub fn set_register(&mut self, reg: Registers, value: u16) -> Result<(), String> {
+        self.registers.write(reg, value)
+            .map_err(|e| format!("Failed to write to register: {}", e))
+    }
+
+    
+    /// Word at `address` without device side effects, 0 outside memory
+    fn peek(&self, address: u16) -> u16 {
+        self.memory.peek(address).unwrap_or(0)
+    }
+
+    pub fn read_memory(&self, address: u16) -> Option<u16> {
+        self.memory.read(address)
+    }
+
+    /// Compare memory against `(address, expected)` pairs, returning
+    /// `(address, expected, actual)` for every mismatch
+    pub fn check_memory(&self, expected: &[(u16, u16)]) -> Vec<(u16, u16, u16)> {
+        expected
+            .iter()
+            .filter_map(|&(address, want)| {
+                let actual = self.peek(address);
+                (actual != want).then_some((address, want, actual))
+            })
+            .collect()
+    }
+
+    pub fn memory_view(&self) -> MemoryView<'_> {
+        MemoryView::new(&self.memory)
+    }
+
+  
+    pub fn write_memory(&mut self, address: u16, value: u16) -> Result<(), String> {
+        self.memory.write(address, value)
+            .map_err(|_| "Failed to write to memory".to_string())
+    }
+
+    /// Address of the last executed instruction that updated COND, if any
+    pub fn last_cc_setter(&self) -> Option<u16> {
+        self.last_cc_setter
+    }
+
+    /// Stores into `start..=end` fail with "write to read-only memory";
+    /// `write_memory` and program loading are not affected
+    pub fn mark_readonly(&mut self, start: u16, end: u16) {
+        self.memory.mark_readonly(start, end);
+    }
+
+    /// Connect a keyboard to the memory-mapped KBSR/KBDR registers
+    pub fn attach_keyboard(&mut self, keyboard: Keyboard) {
+        self.memory.attach_keyboard(keyboard);
+    }
+
+    /// Connect a display to DSR/DDR; characters stored to DDR go to the same `Io` as trap output
+    pub fn attach_display(&mut self) {
+        self.memory.attach_display(Display::new());
+    }
+
+    /// Turn on the non-standard trap vectors (see `TRAP_GETS` and `TRAP_PUTHEX`)
+    pub fn enable_extended_traps(&mut self, enabled: bool) {
+        self.extended_traps = enabled;
+    }
+
+    /// Run TRAP like the hardware: push PSR and PC on the supervisor stack (switching
+    /// to it from user mode) and jump to the routine whose address is stored at
+    /// `memory[trap_vector]`; routines return with RTI. Off by default,
+    /// in which case the standard traps are emulated by the VM itself
+    pub fn set_trap_table_dispatch(&mut self, enabled: bool) {
+        self.trap_table_dispatch = enabled;
+    }
+
+    /// Processor status: privilege (bit 15), priority (bits 10:8) and the condition codes
+    pub fn psr(&self) -> u16 {
+        let cc = self.registers.get_condition_code() & 0x7;
+        (self.psr & (PSR_USER_MODE | PSR_PRIORITY_MASK)) | cc
+    }
+
+    /// Whether the PSR privilege bit is set; the VM starts in supervisor mode
+    pub fn is_user_mode(&self) -> bool {
+        self.psr & PSR_USER_MODE != 0
+    }
+
+    /// Load privilege, priority and condition codes from a PSR value, swapping R6
+    /// between the user and supervisor stacks when the privilege changes
+    pub fn set_psr(&mut self, psr: u16) {
+        let was_user = self.psr & PSR_USER_MODE != 0;
+        let to_user = psr & PSR_USER_MODE != 0;
+        if to_user && !was_user {
+            self.saved_ssp = self.register(Registers::R6);
+            let _ = self.registers.write(Registers::R6, self.saved_usp);
+        } else if was_user && !to_user {
+            self.saved_usp = self.register(Registers::R6);
+            let _ = self.registers.write(Registers::R6, self.saved_ssp);
+        }
+
+        self.psr = psr & (PSR_USER_MODE | PSR_PRIORITY_MASK);
+        let _ = self.registers.write(Registers::COND, psr & 0x7);
+    }
+
+    /// Value R6 takes on the next switch from user to supervisor mode
+    pub fn set_supervisor_stack(&mut self, ssp: u16) {
+        self.saved_ssp = ssp;
+    }
+
+    /// Registers modified by the last step as `(register, old, new)`, PC included
+    pub fn register_changes(&self) -> Vec<(Registers, u16, u16)> {
+        self.register_changes.clone()
+    }
+
+    pub fn set_res_policy(&mut self, policy: ResPolicy) {
+        self.res_policy = policy;
+    }
+
+    /// Called before every fetch while the VM is running
+    pub fn set_pre_hook(&mut self, hook: impl FnMut(&LC3VM) + 'static) {
+        self.pre_hook = Some(Box::new(hook));
+    }
+
+    /// Called after every executed step, including ones that halt or error
+    pub fn set_post_hook(&mut self, hook: impl FnMut(&LC3VM) + 'static) {
+        self.post_hook = Some(Box::new(hook));
+    }
+
+    /// Strict mode turns likely program bugs into errors (e.g. fetching from device space)
+    /// and raises warnings for suspicious ones (e.g. LDR/STR off a zero base register)
+    pub fn set_strict_mode(&mut self, strict: bool) {
+        self.strict = strict;
+    }
+
+    /// Run `handler` for trap vectors that are neither standard nor enabled extensions,
+    /// instead of failing with "Unknown trap vector" (see `trap_helpers`)
+    pub fn on_unknown_trap(&mut self, handler: UnknownTrapHandler) {
+        self.unknown_trap = Some(handler);
+    }
+
+    pub fn on_trap(&mut self, observer: TrapObserver) {
+        self.trap_observer = Some(observer);
+    }
+
+    /// Keep the last `capacity` executed instructions; 0 turns tracing off
+    pub fn enable_trace_buffer(&mut self, capacity: usize) {
+        self.trace_capacity = capacity;
+        while self.trace_buffer.len() > capacity {
+            self.trace_buffer.pop_front();
+        }
+    }
+
+    /// Journal the last `depth` steps so `step_back` can undo them; 0 turns journaling off
+    pub fn enable_journal(&mut self, depth: usize) {
+        self.journal_depth = depth;
+        while self.journal.len() > depth {
+            self.journal.pop_front();
+        }
+    }
+
+    pub fn trace_buffer(&self) -> Vec<TraceRecord> {
+        self.trace_buffer.iter().copied().collect()
+    }
+
+    pub fn on_warning(&mut self, handler: WarningHandler) {
+        self.warning_handler = Some(handler);
+    }
+
+    /// Declare `start..=end` as code so loads and stores touching it raise
+    /// `Warning::CodeRegionAccess`; `None` turns the check off.
+    /// `load_assembled` sets it from the assembler
+    pub fn set_code_region(&mut self, region: Option<(u16, u16)>) {
+        self.code_region = region;
+    }
+
+    /// Turn on the advisory calling-convention checks: R6-relative LDR/STR with a
+    /// positive offset, and a second call made before R7 from the first was saved
+    /// (by a store of R7 or a RET). Both report through `on_warning`
+    pub fn set_stack_checks(&mut self, enabled: bool) {
+        self.stack_checks = enabled;
+        self.unsaved_call = None;
+    }
+
+    /// Replace the cost model used by `get_cycle_count`
+    pub fn set_cycle_model(&mut self, model: CycleModel) {
+        self.cycle_model = model;
+    }
+
+    /// Approximate clock cycles spent since the program was initialized
+    pub fn get_cycle_count(&self) -> u64 {
+        self.cycle_count
+    }
+
+    /// Replace the per-opcode cost table used by `energy_estimate`
+    pub fn set_energy_costs(&mut self, costs: [u64; 16]) {
+        self.energy_costs = costs;
+    }
+
+    /// Total notional energy spent since the program was initialized
+    pub fn energy_estimate(&self) -> u64 {
+        self.energy_used
+    }
+
+    /// Write `value` to `address` once `at_instruction` instructions have executed,
+    /// modelling a device that updates memory behind the program's back
+    pub fn schedule_write(&mut self, at_instruction: u64, address: u16, value: u16) {
+        self.scheduled_writes.push((at_instruction, address, value));
+    }
+
+    /// GETC and IN leave COND alone, as on real hardware; enable this for courses
+    /// that expect the older behavior of setting COND from the character read into R0
+    pub fn set_legacy_trap_cc(&mut self, enabled: bool) {
+        self.legacy_trap_cc = enabled;
+    }
+
+    /// Executed instruction count indexed by opcode value, since `initialize`
+    pub fn opcode_histogram(&self) -> [u64; 16] {
+        self.opcode_counts
+    }
+
+    /// Percentage of executed instructions per opcode, most frequent first;
+    /// opcodes that never ran are left out
+    pub fn instruction_mix(&self) -> Vec<(Opcodes, f64)> {
+        let total: u64 = self.opcode_counts.iter().sum();
+        if total == 0 {
+            return Vec::new();
+        }
+
+        let mut mix: Vec<(Opcodes, f64)> = (0..16u16)
+            .filter_map(|value| {
+                Some((Opcodes::from_u16(value)?, self.opcode_counts[value as usize]))
+            })
+            .filter(|&(_, count)| count > 0)
+            .map(|(opcode, count)| (opcode, count as f64 * 100.0 / total as f64))
+            .collect();
+        mix.sort_by(|a, b| b.1.total_cmp(&a.1));
+        mix
+    }
+
+    /// Data address computed by the last LD/LDR/LDI/ST/STR/STI (the final address
+    /// for the indirect forms); None if the last instruction was anything else
+    pub fn last_effective_address(&self) -> Option<u16> {
+        self.last_effective_address
+    }
+
+    /// Longest string PUTS/PUTSP will print before giving up with an error,
+    /// which catches strings missing their terminator
+    pub fn set_max_string_length(&mut self, max: usize) {
+        self.max_string_length = max;
+    }
+
+    /// Log every executed instruction to `sink` as PC, raw word, disassembly and the
+    /// registers it changed; the sink is flushed when the program halts
+    pub fn enable_trace(&mut self, sink: Box<dyn Write>) {
+        self.trace_sink = Some(sink);
+    }
+
+    pub fn disable_trace(&mut self) {
+        self.flush_trace();
+        self.trace_sink = None;
+    }
+
+    /// Start (or stop) recording the final value of every word the program stores
+    pub fn enable_write_log(&mut self, enabled: bool) {
+        self.write_log = enabled.then(BTreeMap::new);
+    }
+
+    /// Start (or stop) recording every address fetched from or loaded/stored by the program
+    pub fn enable_access_tracking(&mut self, enabled: bool) {
+        self.accessed = enabled.then(BTreeSet::new);
+    }
+
+    /// Start (or stop) collecting each distinct instruction word the program executes
+    pub fn enable_unique_instructions(&mut self, enabled: bool) {
+        self.unique_instructions = enabled.then(BTreeMap::new);
+    }
+
+    /// Every distinct instruction word executed so far with its disassembly
+    /// (PC-relative targets as seen at the first execution);
+    /// empty unless enabled with `enable_unique_instructions`
+    pub fn unique_instructions(&self) -> BTreeMap<u16, String> {
+        self.unique_instructions.clone().unwrap_or_default()
+    }
+
+    /// Lowest and highest tracked address plus the number of distinct addresses touched;
+    /// `(0, 0, 0)` if tracking is off or nothing has run yet
+    pub fn memory_footprint(&self) -> (u16, u16, usize) {
+        match self.accessed.as_ref() {
+            Some(accessed) if !accessed.is_empty() => (
+                accessed.first().copied().unwrap_or(0),
+                accessed.last().copied().unwrap_or(0),
+                accessed.len(),
+            ),
+            _ => (0, 0, 0),
+        }
+    }
+
+    /// Recorded writes as assembly, one `.ORIG`/`.FILL`/`.END` block per run of
+    /// consecutive addresses
+    pub fn memory_writes_as_asm(&self) -> String {
+        let mut asm = String::new();
+        let mut next_address: Option<u16> = None;
+
+        for (&address, &value) in self.write_log.iter().flatten() {
+            if next_address != Some(address) {
+                if next_address.is_some() {
+                    asm.push_str(".END\n");
+                }
+                asm.push_str(&format!(".ORIG x{:04X}\n", address));
+            }
+            asm.push_str(&format!(".FILL x{:04X}\n", value));
+            next_address = address.checked_add(1);
+        }
+        if !asm.is_empty() {
+            asm.push_str(".END\n");
+        }
+
+        asm
+    }
+
+    /// Route trap input/output through `io` instead of the real console
+    pub fn set_io(&mut self, io: Box<dyn Io>) {
+        self.io = io;
+    }
+
+   
+    pub fn get_instruction_count(&self) -> u64 {
+        self.instruction_count
+    }
+
+ 
+    pub fn is_running(&self) -> bool {
+        self.running
+    }
+
+   
+    pub fn halt(&mut self) {
+        self.running = false;
+        self.halt_status = HaltStatus::HaltedCleanly;
+        self.flush_trace();
+    }
+
+    /// How the VM stopped, kept after the run loop that stopped it has returned
+    pub fn halt_status(&self) -> HaltStatus {
+        self.halt_status.clone()
+    }
+
+   
+    pub fn reset(&mut self) {
+        self.registers = RegisterFile::new();
+        self.memory = Memory::with_size(self.memory.size());
+        self.running = false;
+        self.instruction_count = 0;
+        self.last_cc_setter = None;
+        self.register_changes.clear();
+        self.symbols.clear();
+        self.trace_buffer.clear();
+        self.journal.clear();
+        self.energy_used = 0;
+        self.cycle_count = 0;
+        self.scheduled_writes.clear();
+        self.opcode_counts = [0; 16];
+        self.entry_point = 0;
+        self.program_length = 0;
+        self.unsaved_call = None;
+        self.return_stack.clear();
+        self.psr = 0;
+        self.saved_ssp = DEFAULT_SUPERVISOR_STACK;
+        self.saved_usp = 0;
+        self.clear_run_logs();
+        self.halt_status = HaltStatus::NotStarted;
+    }
+
+    /// Empty the per-run logs, leaving enabled ones enabled
+    fn clear_run_logs(&mut self) {
+        if let Some(log) = self.write_log.as_mut() {
+            log.clear();
+        }
+        if let Some(accessed) = self.accessed.as_mut() {
+            accessed.clear();
+        }
+        if let Some(seen) = self.unique_instructions.as_mut() {
+            seen.clear();
+        }
+        self.last_effective_address = None;
+        self.last_store = None;
+        self.watch_hit = None;
+        self.last_executed = None;
+    }
+
+   
+    pub fn debug_info(&self) -> String {
+        format!(
+            "LC-3 VM State:\n\
+            PC: 0x{:04X}\n\
+            R0: 0x{:04X}  R1: 0x{:04X}  R2: 0x{:04X}  R3: 0x{:04X}\n\
+            R4: 0x{:04X}  R5: 0x{:04X}  R6: 0x{:04X}  R7: 0x{:04X}\n\
+            COND: 0x{:04X}\n\
+            Instructions executed: {}\n\
+            Running: {}\n\
+            Call depth: {}\n\
+            Flags: {}\n\
+            Next: {}",
+            self.get_pc(),
+            self.get_register(Registers::R0).unwrap_or(0),
+            self.get_register(Registers::R1).unwrap_or(0),
+            self.get_register(Registers::R2).unwrap_or(0),
+            self.get_register(Registers::R3).unwrap_or(0),
+            self.get_register(Registers::R4).unwrap_or(0),
+            self.get_register(Registers::R5).unwrap_or(0),
+            self.get_register(Registers::R6).unwrap_or(0),
+            self.get_register(Registers::R7).unwrap_or(0),
+            self.get_register(Registers::COND).unwrap_or(0),
+            self.instruction_count,
+            self.running,
+            self.return_stack.len(),
+            Flags::describe(self.registers.get_condition_code()),
+            // Peek at the raw word so a PC in device space doesn't consume keyboard input
+            self.memory
+                .get_memory_slice(self.get_pc() as usize, 1)
+                .first()
+                .map_or("<out of range>".to_string(), |&word| disassemble(word, self.get_pc()))
+        )
+    }
+}
+
+impl std::fmt::Debug for LC3VM {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("LC3VM")
+            .field("registers", &self.registers)
+            .field("memory", &self.memory)
+            .field("running", &self.running)
+            .field("instruction_count", &self.instruction_count)
+            .finish_non_exhaustive()
+    }
+}
 
 impl Default for LC3VM {
     fn default() -> Self {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use super::*;
+    use crate::assembler::{assemble, assemble_program};
+    use crate::types::{DDR, KBDR};
+
+    fn load(source: &str) -> LC3VM {
+        let (origin, program) = assemble(source).unwrap();
+        let mut vm = LC3VM::new();
+        vm.initialize(origin, &program).unwrap();
+        vm
+    }
+
+    #[test]
+    fn step_effects_reports_a_load_without_memory_writes() {
+        let mut vm = load(
+            ".ORIG x3000
+                LD R1, VALUE
+                HALT
+            VALUE .FILL #-3
+            .END",
+        );
+
+        let effects = vm.step_effects().unwrap();
+
+        assert_eq!(effects.pc, (0x3000, 0x3001));
+        assert_eq!(effects.register_writes, vec![(Registers::R1, (-3i16) as u16)]);
+        assert_eq!(effects.flags, Some((Flags::ZRO as u16, Flags::NEG as u16)));
+        assert_eq!(effects.effective_address, Some(0x3002));
+        assert!(effects.memory_writes.is_empty());
+        assert_eq!(effects.trap, None);
+    }
+
+    #[test]
+    fn step_effects_reports_the_value_stored_to_a_device() {
+        let mut vm = load(
+            ".ORIG x3000
+                LD R0, CHAR
+                STI R0, DDR_ADDR
+                HALT
+            CHAR .FILL x41
+            DDR_ADDR .FILL xFE06
+            .END",
+        );
+        let io = BufferIo::new(b"");
+        let output = io.output_handle();
+        vm.set_io(Box::new(io));
+        vm.attach_display();
+
+        vm.step_effects().unwrap();
+        let effects = vm.step_effects().unwrap();
+
+        assert_eq!(effects.memory_writes, vec![(DDR, 0x41)]);
+        assert_eq!(*output.borrow(), b"A");
+        assert!(effects.register_writes.is_empty());
+        assert_eq!(effects.flags, None);
+    }
+
+    #[test]
+    fn run_to_next_store_stops_at_each_store_in_turn() {
+        let mut vm = load(
+            ".ORIG x3000
+                AND R0, R0, #0
+                ADD R0, R0, #5
+                ST R0, FIRST
+                ADD R0, R0, #2
+                LEA R1, SECOND
+                STR R0, R1, #0
+                HALT
+            FIRST .FILL #0
+            SECOND .FILL #0
+            .END",
+        );
+
+        assert_eq!(vm.run_to_next_store(10).unwrap(), Some((0x3007, 5)));
+        assert_eq!(vm.get_pc(), 0x3003);
+        assert_eq!(vm.run_to_next_store(10).unwrap(), Some((0x3008, 7)));
+        assert_eq!(vm.get_pc(), 0x3006);
+        assert_eq!(vm.run_to_next_store(10).unwrap(), None);
+        assert_eq!(vm.halt_status(), HaltStatus::HaltedCleanly);
+    }
+
+    #[test]
+    fn memory_writes_as_asm_replays_the_recorded_stores() {
+        let mut vm = load(
+            ".ORIG x3000
+                LEA R1, CELLS
+                ADD R0, R0, #4
+                STR R0, R1, #0
+                ADD R0, R0, #-5
+                STR R0, R1, #1
+                ST R1, LAST
+                HALT
+            CELLS .BLKW 2
+            LAST .FILL #0
+            .END",
+        );
+        vm.enable_write_log(true);
+        vm.run().unwrap();
+
+        let asm = vm.memory_writes_as_asm();
+        assert_eq!(asm, ".ORIG x3007\n.FILL x0004\n.FILL xFFFF\n.FILL x3007\n.END\n");
+
+        let (origin, words) = assemble(&asm).unwrap();
+        let mut replay = LC3VM::new();
+        replay.initialize(origin, &words).unwrap();
+        for address in 0x3007..0x300A {
+            assert_eq!(replay.read_memory(address), vm.read_memory(address));
+        }
+    }
+
+    #[test]
+    fn watchpoint_on_a_device_register_reports_the_stored_value() {
+        let mut vm = load(
+            ".ORIG x3000
+                LD R0, CHAR
+                STI R0, DDR_ADDR
+                HALT
+            CHAR .FILL x42
+            DDR_ADDR .FILL xFE06
+            .END",
+        );
+        vm.set_io(Box::new(BufferIo::new(b"")));
+        vm.attach_display();
+        vm.add_watchpoint(DDR);
+
+        assert_eq!(
+            vm.run_until_breakpoint().unwrap(),
+            BreakReason::Watchpoint { addr: DDR, old: 0, new: 0x42 }
+        );
+        assert_eq!(vm.get_pc(), 0x3002);
+    }
+
+    #[test]
+    fn watchpoint_does_not_consume_a_pending_key() {
+        let mut vm = load(
+            ".ORIG x3000
+                LDI R1, KBDR_ADDR
+                STI R1, KBDR_ADDR
+                HALT
+            KBDR_ADDR .FILL xFE02
+            .END",
+        );
+        let mut keyboard = Keyboard::new();
+        keyboard.press(b'a');
+        keyboard.press(b'b');
+        vm.attach_keyboard(keyboard);
+        vm.add_watchpoint(0xFE02);
+
+        let reason = vm.run_until_breakpoint().unwrap();
+
+        assert_eq!(vm.register(Registers::R1), b'a' as u16);
+        match reason {
+            BreakReason::Watchpoint { addr, new, .. } => {
+                assert_eq!((addr, new), (0xFE02, b'a' as u16))
+            }
+            other => panic!("expected a watchpoint, got {:?}", other),
+        }
+        vm.step().unwrap();
+        assert_eq!(vm.read_memory(0xFE02), Some(b'b' as u16));
+    }
+
+    #[test]
+    fn interrupt_entry_and_rti_round_trip_and_step_back() {
+        let mut vm = load(
+            ".ORIG x3000
+            LOOP ADD R1, R1, #1
+                BRnzp LOOP
+            .END",
+        );
+        // Handler: LDI R0, [xFE02]; RTI
+        vm.write_memory(0x1000, 0xA002).unwrap();
+        vm.write_memory(0x1001, 0x8000).unwrap();
+        vm.write_memory(0x1003, 0xFE02).unwrap();
+        vm.write_memory(INTERRUPT_VECTOR_TABLE + KEYBOARD_INTERRUPT, 0x1000).unwrap();
+        vm.set_register(Registers::R6, 0x3000).unwrap();
+        vm.set_psr(PSR_USER_MODE);
+        vm.enable_journal(4);
+        let mut keyboard = Keyboard::new();
+        keyboard.press(b'k');
+        keyboard.set_status(0x4000);
+        vm.attach_keyboard(keyboard);
+        let user_psr = vm.psr();
+
+        let info = vm.step_detailed().unwrap();
+
+        assert_eq!((info.pc, info.instruction), (0x1000, 0xA002));
+        assert_eq!(vm.register(Registers::R0), b'k' as u16);
+        assert_eq!(vm.register(Registers::R6), 0x2FFE);
+        assert_eq!(vm.read_memory(0x2FFF), Some(user_psr));
+        assert_eq!(vm.read_memory(0x2FFE), Some(0x3000));
+        assert!(!vm.is_user_mode());
+
+        let effects = vm.step_effects().unwrap();
+
+        assert_eq!(effects.pc, (0x1001, 0x3000));
+        assert_eq!(vm.register(Registers::R6), 0);
+        assert_eq!(vm.psr(), user_psr);
+
+        vm.step_back().unwrap();
+        vm.step_back().unwrap();
+
+        assert_eq!(vm.get_pc(), 0x3000);
+        assert_eq!(vm.register(Registers::R6), 0);
+        assert_eq!(vm.register(Registers::R0), 0);
+        assert_eq!(vm.psr(), user_psr);
+        assert_eq!(vm.read_memory(0x2FFF), Some(0));
+        assert_eq!(vm.read_memory(0x2FFE), Some(0));
+        vm.set_psr(0);
+        assert_eq!(vm.register(Registers::R6), 0x3000);
+    }
+
+    fn enter_user_mode(vm: &mut LC3VM) {
+        vm.set_register(Registers::R6, 0x3000).unwrap();
+        vm.set_psr(PSR_USER_MODE);
+    }
+
+    #[test]
+    fn user_mode_rti_raises_the_privilege_exception_through_x0100() {
+        let mut vm = load(".ORIG x3000\nRTI\n.END");
+        vm.write_memory(INTERRUPT_VECTOR_TABLE + PRIVILEGE_MODE_EXCEPTION, 0x1000).unwrap();
+        enter_user_mode(&mut vm);
+        let user_psr = vm.psr();
+
+        vm.step().unwrap();
+
+        assert_eq!(vm.get_pc(), 0x1000);
+        assert!(!vm.is_user_mode());
+        assert_eq!(vm.register(Registers::R6), 0x2FFE);
+        assert_eq!(vm.read_memory(0x2FFF), Some(user_psr));
+        assert_eq!(vm.read_memory(0x2FFE), Some(0x3001));
+    }
+
+    #[test]
+    fn user_mode_load_from_system_space_raises_an_access_violation() {
+        let mut vm = load(
+            ".ORIG x3000
+                LDR R0, R1, #0
+            .END",
+        );
+        vm.write_memory(INTERRUPT_VECTOR_TABLE + ACCESS_CONTROL_VIOLATION, 0x1000).unwrap();
+        vm.write_memory(0x2000, 0x1234).unwrap();
+        vm.set_register(Registers::R1, 0x2000).unwrap();
+        enter_user_mode(&mut vm);
+
+        vm.step().unwrap();
+
+        assert_eq!(vm.get_pc(), 0x1000);
+        assert_eq!(vm.register(Registers::R0), 0);
+        assert!(!vm.is_user_mode());
+    }
+
+    #[test]
+    fn blocked_user_mode_store_is_not_reported_as_a_write() {
+        let mut vm = load(
+            ".ORIG x3000
+                STR R0, R1, #0
+            .END",
+        );
+        vm.write_memory(INTERRUPT_VECTOR_TABLE + ACCESS_CONTROL_VIOLATION, 0x1000).unwrap();
+        vm.write_memory(0x1000, 0xF025).unwrap();
+        vm.set_register(Registers::R0, 0x55).unwrap();
+        vm.set_register(Registers::R1, 0x2000).unwrap();
+        vm.add_watchpoint(0x2000);
+        vm.enable_write_log(true);
+        enter_user_mode(&mut vm);
+
+        assert_eq!(vm.run_until_breakpoint(), Ok(BreakReason::Halted));
+
+        assert_eq!(vm.read_memory(0x2000), Some(0));
+        assert_eq!(vm.memory_writes_as_asm(), "");
+    }
+
+    #[test]
+    fn blocked_user_mode_call_is_not_tracked() {
+        let mut vm = load(
+            ".ORIG x3000
+                JMP R1
+            .END",
+        );
+        vm.write_memory(INTERRUPT_VECTOR_TABLE + ACCESS_CONTROL_VIOLATION, 0x1000).unwrap();
+        // JSR #0 in system space
+        vm.write_memory(0x2000, 0x4800).unwrap();
+        vm.set_register(Registers::R1, 0x2000).unwrap();
+        enter_user_mode(&mut vm);
+
+        vm.run_for(2).unwrap();
+
+        assert_eq!(vm.get_pc(), 0x1000);
+        assert!(vm.return_stack().is_empty());
+    }
+
+    #[test]
+    fn user_mode_fetch_outside_user_space_raises_an_access_violation() {
+        let mut vm = load(
+            ".ORIG x3000
+                JMP R1
+            .END",
+        );
+        vm.write_memory(INTERRUPT_VECTOR_TABLE + ACCESS_CONTROL_VIOLATION, 0x1000).unwrap();
+        // ADD R0, R0, #1 in system space
+        vm.write_memory(0x2000, 0x1021).unwrap();
+        vm.set_register(Registers::R1, 0x2000).unwrap();
+        enter_user_mode(&mut vm);
+
+        vm.step().unwrap();
+        assert_eq!(vm.get_pc(), 0x2000);
+        vm.step().unwrap();
+
+        assert_eq!(vm.get_pc(), 0x1000);
+        assert_eq!(vm.register(Registers::R0), 0);
+        assert_eq!(vm.read_memory(0x2FFE), Some(0x2001));
+    }
+
+    #[test]
+    fn user_mode_trap_runs_its_routine_in_supervisor_mode() {
+        let mut vm = load(
+            ".ORIG x3000
+                TRAP x40
+                HALT
+            .END",
+        );
+        // Routine: ADD R0, R0, #1; RTI
+        vm.write_memory(0x0040, 0x1000).unwrap();
+        vm.write_memory(0x1000, 0x1021).unwrap();
+        vm.write_memory(0x1001, 0x8000).unwrap();
+        vm.set_trap_table_dispatch(true);
+        enter_user_mode(&mut vm);
+
+        vm.step().unwrap();
+        assert_eq!(vm.get_pc(), 0x1000);
+        assert!(!vm.is_user_mode());
+        assert_eq!(vm.register(Registers::R7), 0);
+
+        vm.step().unwrap();
+        vm.step().unwrap();
+
+        assert_eq!(vm.register(Registers::R0), 1);
+        assert_eq!(vm.get_pc(), 0x3001);
+        assert!(vm.is_user_mode());
+        assert_eq!(vm.register(Registers::R6), 0);
+    }
+
+    #[test]
+    fn supervisor_trap_uses_the_same_routine_convention() {
+        let mut vm = load(
+            ".ORIG x3000
+                TRAP x40
+                HALT
+            .END",
+        );
+        // Routine: ADD R0, R0, #1; RTI
+        vm.write_memory(0x0040, 0x1000).unwrap();
+        vm.write_memory(0x1000, 0x1021).unwrap();
+        vm.write_memory(0x1001, 0x8000).unwrap();
+        vm.set_trap_table_dispatch(true);
+        vm.set_register(Registers::R6, 0x2000).unwrap();
+
+        vm.step().unwrap();
+        assert_eq!(vm.get_pc(), 0x1000);
+        assert_eq!(vm.register(Registers::R7), 0);
+        assert_eq!(vm.register(Registers::R6), 0x1FFE);
+        assert_eq!(vm.read_memory(0x1FFE), Some(0x3001));
+
+        vm.step().unwrap();
+        vm.step().unwrap();
+
+        assert_eq!(vm.register(Registers::R0), 1);
+        assert_eq!(vm.get_pc(), 0x3001);
+        assert!(!vm.is_user_mode());
+        assert_eq!(vm.register(Registers::R6), 0x2000);
+    }
+
+    #[test]
+    fn trap_table_dispatch_rejects_an_empty_vector() {
+        let mut vm = load(".ORIG x3000\nTRAP x22\n.END");
+        vm.set_trap_table_dispatch(true);
+
+        assert_eq!(vm.step(), Err("no handler installed for trap x22".to_string()));
+        assert!(!vm.is_running());
+    }
+
+    #[test]
+    fn load_state_restores_stacks_and_halt_status() {
+        let mut vm = load(".ORIG x3000\nHALT\n.END");
+        enter_user_mode(&mut vm);
+        vm.run().unwrap();
+        let halted = vm.save_state();
+
+        vm.set_psr(0);
+        vm.set_supervisor_stack(0x2000);
+        vm.load_state(&halted);
+
+        assert_eq!(vm.halt_status(), HaltStatus::HaltedCleanly);
+        assert!(vm.is_user_mode());
+        vm.set_psr(0);
+        assert_eq!(vm.register(Registers::R6), 0x3000);
+    }
+
+    #[test]
+    fn reset_and_initialize_clear_the_run_logs() {
+        let mut vm = load(".ORIG x3000\nST R0, CELL\nHALT\nCELL .FILL #1\n.END");
+        vm.enable_write_log(true);
+        vm.enable_access_tracking(true);
+        vm.enable_unique_instructions(true);
+        vm.run().unwrap();
+        assert!(!vm.memory_writes_as_asm().is_empty());
+
+        vm.initialize(0x3000, &[0xF025]).unwrap();
+
+        assert_eq!(vm.memory_writes_as_asm(), "");
+        assert_eq!(vm.memory_footprint().2, 0);
+        assert!(vm.unique_instructions().is_empty());
+        assert_eq!(vm.last_effective_address(), None);
+
+        vm.run().unwrap();
+        assert_eq!(vm.unique_instructions().len(), 1);
+        vm.reset();
+        assert!(vm.unique_instructions().is_empty());
+    }
+
+    #[test]
+    fn snapshot_diff_reports_register_memory_and_halt_changes() {
+        let mut vm = load(
+            ".ORIG x3000
+                LD R1, VALUE
+                STI R1, TARGET
+                HALT
+            VALUE .FILL x0042
+            TARGET .FILL x4000
+            .END",
+        );
+        vm.step().unwrap();
+        let before = vm.save_state();
+        vm.run().unwrap();
+
+        let diff = before.diff(&vm.save_state());
+
+        assert_eq!(diff.registers, vec![(Registers::PC, 0x3001, 0x3003)]);
+        assert_eq!(
+            diff.report(),
+            "PC: 0x3001 -> 0x3003\nmem[x4000]: 0x0000 -> 0x0042\nhalted: false -> true"
+        );
+    }
+
+    #[test]
+    fn snapshot_diff_report_lists_a_register_change() {
+        let mut vm = load(".ORIG x3000\nADD R1, R1, #2\nHALT\n.END");
+        vm.set_register(Registers::R1, 5).unwrap();
+        let before = vm.save_state();
+        vm.step().unwrap();
+        let after = vm.save_state();
+
+        let diff = before.diff(&after);
+
+        assert!(before.diff(&before).is_empty());
+        assert_eq!(
+            diff.report(),
+            "R1: 0x0005 -> 0x0007\nPC: 0x3000 -> 0x3001\nCOND: 0x0002 -> 0x0001"
+        );
+    }
+
+    #[test]
+    fn run_cycles_stops_before_an_instruction_that_would_overrun() {
+        let mut vm = load(
+            ".ORIG x3000
+                ADD R0, R0, #1
+                LD R1, VALUE
+                ADD R0, R0, #1
+                HALT
+            VALUE .FILL #3
+            .END",
+        );
+
+        // ADD costs 1 and LD 4 under the default model: 5 cycles fit ADD and LD only
+        assert_eq!(vm.run_cycles(5), RunOutcome::StepLimitReached);
+        assert_eq!(vm.get_cycle_count(), 5);
+        assert_eq!(vm.get_pc(), 0x3002);
+        assert_eq!(vm.halt_status(), HaltStatus::StepLimitReached);
+
+        assert_eq!(vm.run_cycles(1), RunOutcome::StepLimitReached);
+        assert_eq!(vm.get_pc(), 0x3003);
+        assert_eq!(vm.run_cycles(100), RunOutcome::Halted);
+    }
+
+    #[test]
+    fn counted_breakpoint_fires_on_its_third_hit() {
+        let mut vm = load(
+            ".ORIG x3000
+                AND R0, R0, #0
+            LOOP ADD R0, R0, #1
+                ADD R1, R0, #-5
+                BRn LOOP
+                HALT
+            .END",
+        );
+        vm.add_breakpoint_count(0x3001, 2);
+
+        assert_eq!(vm.run_until_breakpoint().unwrap(), BreakReason::Breakpoint(0x3001));
+        assert_eq!(vm.register(Registers::R0), 2);
+        assert_eq!(vm.breakpoint_hits(0x3001), Some(3));
+
+        assert_eq!(vm.run_until_breakpoint().unwrap(), BreakReason::Breakpoint(0x3001));
+        assert_eq!(vm.register(Registers::R0), 3);
+    }
+
+    #[test]
+    fn run_with_timer_enters_the_handler_every_period() {
+        let mut vm = load(
+            ".ORIG x3000
+            SPIN BRnzp SPIN
+            .END",
+        );
+        // Handler: ADD R2, R2, #1; RTI
+        vm.write_memory(0x1000, 0x14A1).unwrap();
+        vm.write_memory(0x1001, 0x8000).unwrap();
+        vm.set_register(Registers::R6, 0x2000).unwrap();
+
+        // Fires before instructions 6, 11, 16, 21 and 26: every 5 instructions, handler included
+        assert_eq!(vm.run_with_timer(5, 0x1000, 30), RunOutcome::StepLimitReached);
+
+        assert_eq!(vm.register(Registers::R2), 5);
+        assert_eq!(vm.get_pc(), 0x3000);
+        assert_eq!(vm.register(Registers::R6), 0x2000);
+        assert_eq!(vm.psr() & PSR_PRIORITY_MASK, 0);
+    }
+
+    #[test]
+    fn execute_reports_adder_output_registers_and_status() {
+        let (origin, program) = assemble(
+            ".ORIG x3000
+                IN
+                ADD R1, R0, #0
+                IN
+                ADD R0, R0, R1
+                LD R2, NEG_ZERO
+                ADD R0, R0, R2
+                OUT
+                HALT
+            NEG_ZERO .FILL #-48
+            .END",
+        )
+        .unwrap();
+
+        let report = LC3VM::execute(&program, origin, b"34", 100).unwrap();
+
+        assert_eq!(report.output, b"347");
+        assert_eq!(report.registers[Registers::R0 as usize], b'7' as u16);
+        assert_eq!(report.registers[Registers::R1 as usize], b'3' as u16);
+        assert_eq!(report.registers[Registers::R2 as usize], (-48i16) as u16);
+        assert_eq!(report.registers[Registers::PC as usize], 0x3008);
+        assert_eq!(report.registers[Registers::COND as usize], Flags::POS as u16);
+        assert_eq!(report.stop_reason, HaltStatus::HaltedCleanly);
+        assert_eq!(report.instruction_count, 8);
+    }
+
+    #[test]
+    fn execute_reports_the_step_limit() {
+        // BRnzp to itself
+        let report = LC3VM::execute(&[0x0FFF], 0x3000, b"", 10).unwrap();
+
+        assert!(report.output.is_empty());
+        assert_eq!(report.stop_reason, HaltStatus::StepLimitReached);
+        assert_eq!(report.instruction_count, 10);
+    }
+
+    #[test]
+    fn run_with_stats_counts_a_loop() {
+        let mut vm = load(
+            ".ORIG x3000
+                AND R0, R0, #0
+                ADD R1, R0, #3
+            LOOP ADD R0, R0, #2
+                ADD R1, R1, #-1
+                BRp LOOP
+                HALT
+            .END",
+        );
+
+        let stats = vm.run_with_stats().unwrap();
+
+        assert_eq!(stats.instructions_executed, 12);
+        assert_eq!(stats.opcode_count(Opcodes::ADD), 7);
+        assert_eq!(stats.opcode_count(Opcodes::BR), 3);
+        assert_eq!(stats.branches_taken, 2);
+        assert_eq!(stats.branches_not_taken, 1);
+        assert_eq!(vm.register(Registers::R0), 6);
+    }
+
+    #[test]
+    fn initialize_rejects_an_empty_program_and_leaves_a_clear_state() {
+        let mut vm = LC3VM::new();
+
+        assert_eq!(
+            vm.initialize(0x3000, &[]),
+            Err("Cannot initialize with an empty program".to_string())
+        );
+        assert!(!vm.is_running());
+        assert_eq!(vm.halt_status(), HaltStatus::NotStarted);
+        assert_eq!(vm.get_instruction_count(), 0);
+        assert!(!vm.memory.is_loaded_at(0x3000));
+        assert_eq!(vm.step(), Ok(ExecutionResult::Halt));
+    }
+
+    #[test]
+    fn last_cc_setter_points_at_the_add_before_a_branch() {
+        let mut vm = load(
+            ".ORIG x3000
+                LD R0, VALUE
+                ADD R1, R0, #-1
+                BRz DONE
+            DONE HALT
+            VALUE .FILL #1
+            .END",
+        );
+
+        vm.run_for(3).unwrap();
+
+        assert_eq!(vm.get_pc(), 0x3003);
+        assert_eq!(vm.last_cc_setter(), Some(0x3001));
+    }
+
+    #[test]
+    fn store_into_a_read_only_range_is_an_error() {
+        let mut vm = load(
+            ".ORIG x3000
+                ADD R0, R0, #7
+                ST R0, CELL
+                HALT
+            CELL .FILL #0
+            .END",
+        );
+        vm.mark_readonly(0x3003, 0x3003);
+
+        assert_eq!(vm.run(), Err("write to read-only memory".to_string()));
+        assert_eq!(vm.read_memory(0x3003), Some(0));
+        assert!(matches!(vm.halt_status(), HaltStatus::Errored(_)));
+    }
+
+    #[test]
+    fn run_until_register_stops_when_the_sum_is_reached() {
+        let mut vm = load(
+            ".ORIG x3000
+            LOOP ADD R1, R1, #1
+                ADD R0, R0, R1
+                BRnzp LOOP
+            .END",
+        );
+
+        assert_eq!(vm.run_until_register(Registers::R0, 10, 100), RunOutcome::ConditionMet);
+        assert_eq!(vm.register(Registers::R1), 4);
+        assert_eq!(vm.get_pc(), 0x3002);
+
+        assert_eq!(vm.run_until_register(Registers::R0, 11, 20), RunOutcome::StepLimitReached);
+    }
+
+    #[test]
+    fn check_memory_reports_only_the_mismatch() {
+        let mut vm = load(
+            ".ORIG x3000
+                ADD R0, R0, #5
+                ST R0, FIRST
+                ST R0, SECOND
+                HALT
+            FIRST .FILL #0
+            SECOND .FILL #0
+            .END",
+        );
+        vm.run().unwrap();
+
+        assert_eq!(vm.check_memory(&[(0x3004, 5), (0x3005, 6)]), vec![(0x3005, 6, 5)]);
+    }
+
+    #[test]
+    fn randomized_state_depends_only_on_the_seed() {
+        let first = LC3VM::new_randomized(42).save_state();
+        let second = LC3VM::new_randomized(42).save_state();
+        let other = LC3VM::new_randomized(43).save_state();
+
+        assert_eq!(first, second);
+        assert_ne!(first.registers, other.registers);
+        assert_ne!(first.memory, other.memory);
+    }
+
+    #[test]
+    fn register_changes_list_the_destination_and_pc() {
+        let mut vm = load(".ORIG x3000\nADD R1, R3, #2\nHALT\n.END");
+        vm.set_register(Registers::R3, 9).unwrap();
+        // Already positive, so COND does not change either
+        vm.set_register(Registers::COND, Flags::POS as u16).unwrap();
+
+        vm.step().unwrap();
+
+        assert_eq!(
+            vm.register_changes(),
+            vec![(Registers::R1, 0, 11), (Registers::PC, 0x3000, 0x3001)]
+        );
+    }
+
+    /// ADD R0, R0, #1; RES; ADD R0, R0, #1; HALT
+    const RES_PROGRAM: [u16; 4] = [0x1021, 0xD000, 0x1021, 0xF025];
+
+    #[test]
+    fn res_policy_error_stops_at_the_res_word() {
+        let mut vm = LC3VM::new();
+        vm.initialize(0x3000, &RES_PROGRAM).unwrap();
+
+        assert_eq!(vm.run(), Err("RES instruction is reserved".to_string()));
+        assert_eq!(vm.register(Registers::R0), 1);
+    }
+
+    #[test]
+    fn res_policy_nop_runs_past_the_res_word() {
+        let mut vm = LC3VM::new();
+        vm.initialize(0x3000, &RES_PROGRAM).unwrap();
+        vm.set_res_policy(ResPolicy::Nop);
+
+        vm.run().unwrap();
+
+        assert_eq!(vm.register(Registers::R0), 2);
+        assert_eq!(vm.halt_status(), HaltStatus::HaltedCleanly);
+    }
+
+    #[test]
+    fn register_reads_every_register() {
+        let mut vm = LC3VM::new();
+        for (i, reg) in (0..=Registers::COND as u16).map(Registers::from).enumerate() {
+            vm.set_register(reg, 0x100 + i as u16).unwrap();
+        }
+
+        for (i, reg) in (0..=Registers::COND as u16).map(Registers::from).enumerate() {
+            assert_eq!(vm.register(reg), 0x100 + i as u16, "{}", reg);
+            assert_eq!(vm.get_register(reg), Some(vm.register(reg)));
+        }
+    }
+
+    #[test]
+    fn hooks_run_before_and_after_each_instruction() {
+        let mut vm = load(
+            ".ORIG x3000
+                ADD R0, R0, #1
+                ADD R0, R0, #1
+                HALT
+            .END",
+        );
+        let pre = Rc::new(RefCell::new(Vec::new()));
+        let post = Rc::new(RefCell::new(0));
+        let seen = Rc::clone(&pre);
+        vm.set_pre_hook(move |vm| seen.borrow_mut().push(vm.get_pc()));
+        let count = Rc::clone(&post);
+        vm.set_post_hook(move |_| *count.borrow_mut() += 1);
+
+        vm.run().unwrap();
+
+        assert_eq!(*pre.borrow(), vec![0x3000, 0x3001, 0x3002]);
+        assert_eq!(*post.borrow(), 3);
+    }
+
+    #[test]
+    fn goto_label_moves_the_next_fetch_to_the_label() {
+        let program = assemble_program(
+            ".ORIG x3000
+                ADD R0, R0, #1
+            SKIP ADD R1, R1, #1
+                HALT
+            .END",
+        )
+        .unwrap();
+        let mut vm = LC3VM::new();
+        vm.initialize(program.origin, &program.words).unwrap();
+        for (name, &address) in &program.symbols {
+            vm.add_symbol(name, address);
+        }
+
+        vm.goto_label("SKIP").unwrap();
+        let info = vm.step_detailed().unwrap();
+
+        assert_eq!(info.pc, 0x3001);
+        assert_eq!(vm.register(Registers::R0), 0);
+        assert_eq!(vm.register(Registers::R1), 1);
+        assert!(vm.goto_label("MISSING").is_err());
+    }
+
+    #[test]
+    fn strict_mode_rejects_a_fetch_from_device_space() {
+        let mut vm = load(".ORIG x3000\nHALT\n.END");
+        vm.set_strict_mode(true);
+        vm.set_register(Registers::PC, 0xFE00).unwrap();
+
+        assert_eq!(
+            vm.step(),
+            Err("Instruction fetch from device space at 0xFE00".to_string())
+        );
+        assert!(!vm.is_running());
+    }
+
+    #[test]
+    fn trap_observer_sees_each_trap_with_its_arguments() {
+        let mut vm = load(
+            ".ORIG x3000
+                GETC
+                OUT
+                LEA R0, TEXT
+                PUTS
+                HALT
+            TEXT .STRINGZ \"ok\"
+            .END",
+        );
+        vm.set_io(Box::new(BufferIo::new(b"x")));
+        let calls = Rc::new(RefCell::new(Vec::new()));
+        let seen = Rc::clone(&calls);
+        vm.on_trap(Box::new(move |vector, registers| {
+            seen.borrow_mut().push((vector, registers.read(Registers::R0).unwrap_or(0)));
+        }));
+
+        vm.run().unwrap();
+
+        assert_eq!(
+            *calls.borrow(),
+            vec![
+                (TrapVectors::GETC, 0),
+                (TrapVectors::OUT, b'x' as u16),
+                (TrapVectors::PUTS, 0x3005),
+                (TrapVectors::HALT, 0x3005),
+            ]
+        );
+    }
+
+    #[cfg(feature = "testing")]
+    #[test]
+    fn expect_halt_returns_r0_of_a_halting_program() {
+        let mut vm = LC3VM::new();
+        vm.initialize(0x3000, &crate::programs::add_two(3, 4)).unwrap();
+
+        assert_eq!(vm.expect_halt(100), 7);
+    }
+
+    #[cfg(feature = "testing")]
+    #[test]
+    #[should_panic(expected = "did not halt")]
+    fn expect_halt_panics_on_a_program_that_loops() {
+        let mut vm = LC3VM::new();
+        vm.initialize(0x3000, &[0x0FFF]).unwrap();
+
+        vm.expect_halt(10);
+    }
+
+    #[test]
+    fn call_stack_walks_pushed_return_addresses() {
+        let mut vm = load(
+            ".ORIG x3000
+                LD R6, STACK
+                JSR OUTER
+                HALT
+            OUTER ADD R6, R6, #-1
+                STR R7, R6, #0
+                JSR INNER
+                LDR R7, R6, #0
+                ADD R6, R6, #1
+                RET
+            INNER ADD R6, R6, #-1
+                STR R7, R6, #0
+            PAUSE LDR R7, R6, #0
+                ADD R6, R6, #1
+                RET
+            STACK .FILL x4000
+            .END",
+        );
+        vm.add_breakpoint(0x300B);
+
+        assert_eq!(vm.run_until_breakpoint().unwrap(), BreakReason::Breakpoint(0x300B));
+        assert_eq!(vm.call_stack(0x4000), vec![0x3006, 0x3002]);
+    }
+
+    #[test]
+    fn load_hex_words_loads_and_runs_a_tiny_program() {
+        let mut vm = LC3VM::new();
+
+        // AND R0, R0, #0; ADD R0, R0, #5; HALT
+        vm.load_hex_words(0x3000, "5020 x1025 0xF025").unwrap();
+        vm.run().unwrap();
+
+        assert_eq!(vm.register(Registers::R0), 5);
+        assert_eq!(vm.get_instruction_count(), 3);
+        assert!(vm.load_hex_words(0x3000, "5020 zz").is_err());
+    }
+
+    #[test]
+    fn eval_applies_alu_ops_and_sets_flags() {
+        let mut vm = LC3VM::new();
+
+        let result = vm.eval(&[AluOp::Add(100), AluOp::And(0x00F0), AluOp::Not]);
+
+        assert_eq!(result, 0xFF9F);
+        assert_eq!(vm.register(Registers::COND), Flags::NEG as u16);
+        assert_eq!(vm.eval(&[AluOp::Add(-3), AluOp::Add(3)]), 0);
+        assert_eq!(vm.register(Registers::COND), Flags::ZRO as u16);
+    }
+
+    #[test]
+    fn small_memory_rejects_an_access_past_its_end() {
+        let mut vm = LC3VM::with_memory_size(0x1000);
+        // LDR R0, R1, #0
+        vm.initialize(0x0200, &[0x6040]).unwrap();
+        vm.set_register(Registers::R1, 0x2000).unwrap();
+
+        assert!(vm.step().is_err());
+        assert_eq!(vm.read_memory(0x2000), None);
+        assert!(vm.write_memory(0x2000, 1).is_err());
+        assert_eq!(vm.memory_view().len(), 0x1000);
+    }
+
+    fn collect_warnings(vm: &mut LC3VM) -> Rc<RefCell<Vec<Warning>>> {
+        let warnings = Rc::new(RefCell::new(Vec::new()));
+        let sink = Rc::clone(&warnings);
+        vm.on_warning(Box::new(move |warning| sink.borrow_mut().push(warning.clone())));
+        warnings
+    }
+
+    #[test]
+    fn load_from_the_assembled_code_region_warns() {
+        let program = assemble_program(
+            ".ORIG x3000
+            MAIN LD R0, VALUE
+                LD R1, MAIN
+                HALT
+            VALUE .FILL #5
+            .END",
+        )
+        .unwrap();
+        let mut vm = LC3VM::new();
+        vm.load_assembled(&program).unwrap();
+        let warnings = collect_warnings(&mut vm);
+
+        vm.run().unwrap();
+
+        assert_eq!(vm.register(Registers::R0), 5);
+        assert_eq!(
+            *warnings.borrow(),
+            vec![Warning::CodeRegionAccess { pc: 0x3001, address: 0x3000, store: false }]
+        );
+    }
+
+    #[test]
+    fn loop_costs_more_energy_than_a_single_add() {
+        let mut naive = load(
+            ".ORIG x3000
+                AND R0, R0, #0
+                AND R1, R1, #0
+                ADD R1, R1, #5
+            LOOP ADD R0, R0, #1
+                ADD R1, R1, #-1
+                BRp LOOP
+                HALT
+            .END",
+        );
+        let mut optimized = load(
+            ".ORIG x3000
+                AND R0, R0, #0
+                ADD R0, R0, #5
+                HALT
+            .END",
+        );
+        naive.run().unwrap();
+        optimized.run().unwrap();
+
+        assert_eq!(naive.register(Registers::R0), 5);
+        assert_eq!(optimized.register(Registers::R0), 5);
+        // One extra setup ADD plus five iterations of ADD, ADD, BR
+        assert_eq!(naive.energy_estimate() - optimized.energy_estimate(), 1 + 5 * 4);
+    }
+
+    #[test]
+    fn scheduled_write_lands_after_the_fifth_instruction() {
+        let mut vm = load(
+            ".ORIG x3000
+                ADD R0, R0, #1
+                ADD R0, R0, #1
+                ADD R0, R0, #1
+                ADD R0, R0, #1
+                ADD R0, R0, #1
+                ADD R0, R0, #1
+                HALT
+            .END",
+        );
+        vm.schedule_write(5, 0x4000, 0xBEEF);
+
+        for _ in 0..4 {
+            vm.step().unwrap();
+        }
+        assert_eq!(vm.read_memory(0x4000).unwrap(), 0);
+
+        vm.step().unwrap();
+        assert_eq!(vm.read_memory(0x4000).unwrap(), 0xBEEF);
+    }
+
+    #[test]
+    fn getc_leaves_condition_codes_alone() {
+        let mut vm = load(
+            ".ORIG x3000
+                ADD R1, R1, #-1
+                GETC
+                HALT
+            .END",
+        );
+        vm.set_io(Box::new(BufferIo::new(b"A")));
+
+        vm.run_for(2).unwrap();
+
+        assert_eq!(vm.register(Registers::R0), u16::from(b'A'));
+        assert_eq!(vm.register(Registers::COND), Flags::NEG as u16);
+    }
+
+    #[test]
+    fn halt_status_after_halt_and_after_an_error() {
+        let mut vm = LC3VM::new();
+        vm.initialize(0x3000, &[0x1021, 0xF025]).unwrap();
+        assert_eq!(vm.halt_status(), HaltStatus::Running);
+        vm.run().unwrap();
+        assert_eq!(vm.halt_status(), HaltStatus::HaltedCleanly);
+
+        vm.initialize(0x3000, &RES_PROGRAM).unwrap();
+        assert!(vm.run().is_err());
+        assert_eq!(
+            vm.halt_status(),
+            HaltStatus::Errored(LC3Error::Custom("RES instruction is reserved".to_string()))
+        );
+    }
+
+    #[test]
+    fn instruction_mix_sums_to_a_hundred_percent() {
+        let mut vm = load(
+            ".ORIG x3000
+                AND R1, R1, #0
+                ADD R1, R1, #10
+            LOOP ADD R0, R0, #2
+                ADD R1, R1, #-1
+                BRp LOOP
+                HALT
+            .END",
+        );
+        vm.run().unwrap();
+
+        let mix = vm.instruction_mix();
+        let total: f64 = mix.iter().map(|&(_, percent)| percent).sum();
+
+        assert!((total - 100.0).abs() < 1e-9);
+        assert_eq!(mix[0].0, Opcodes::ADD);
+        assert_eq!(mix[1].0, Opcodes::BR);
+        assert_eq!(mix.len(), 4);
+    }
+
+    #[test]
+    fn entry_point_matches_pc_after_initialize() {
+        let mut vm = LC3VM::new();
+        vm.initialize(0x4000, &[0x1021, 0xF025]).unwrap();
+
+        assert_eq!(vm.entry_point(), 0x4000);
+        assert_eq!(vm.get_pc(), vm.entry_point());
+        assert!(vm.verify_pc_at_entry());
+
+        vm.step().unwrap();
+        assert!(!vm.verify_pc_at_entry());
+        assert_eq!(vm.entry_point(), 0x4000);
+    }
+
+    #[test]
+    fn ldr_reports_base_plus_offset() {
+        let mut vm = load(
+            ".ORIG x3000
+                LEA R1, DATA
+                LDR R0, R1, #2
+                HALT
+            DATA .FILL #1
+                .FILL #2
+                .FILL #3
+            .END",
+        );
+
+        vm.step().unwrap();
+        assert_eq!(vm.last_effective_address(), None);
+        vm.step().unwrap();
+
+        assert_eq!(vm.last_effective_address(), Some(0x3005));
+        assert_eq!(vm.register(Registers::R0), 3);
+    }
+
+    #[test]
+    fn layout_reports_an_assembled_program() {
+        let program = assemble_program(
+            ".ORIG x3100
+            MAIN LD R0, VALUE
+                HALT
+            VALUE .FILL #9
+            .END",
+        )
+        .unwrap();
+        let mut vm = LC3VM::new();
+        vm.add_symbol("STALE", 0x4000);
+        vm.load_assembled(&program).unwrap();
+
+        let layout = vm.layout();
+
+        assert_eq!(layout.origin, 0x3100);
+        assert_eq!(layout.length, 3);
+        assert_eq!(layout.code_region, Some((0x3100, 0x3101)));
+        assert_eq!(layout.data_region, Some((0x3102, 0x3102)));
+        assert_eq!(
+            layout.symbols,
+            vec![("MAIN".to_string(), 0x3100), ("VALUE".to_string(), 0x3102)]
+        );
+    }
+
+    #[test]
+    fn out_writes_the_low_byte_of_r0() {
+        let mut vm = load(
+            ".ORIG x3000
+                LD R0, H
+                OUT
+                LD R0, I
+                OUT
+                HALT
+            H .FILL x1248
+            I .FILL x0049
+            .END",
+        );
+        let io = BufferIo::new(b"");
+        let output = io.output_handle();
+        vm.set_io(Box::new(io));
+
+        vm.run().unwrap();
+
+        assert_eq!(output.borrow().as_slice(), b"HI");
+    }
+
+    #[test]
+    fn puts_errors_past_a_custom_string_cap() {
+        let source = ".ORIG x3000
+                LEA R0, TEXT
+                PUTS
+                HALT
+            TEXT .STRINGZ \"HELLO\"
+            .END";
+        let mut vm = load(source);
+        vm.set_max_string_length(4);
+        vm.set_io(Box::new(BufferIo::new(b"")));
+
+        assert_eq!(vm.run(), Err("PUTS string exceeds maximum length of 4".to_string()));
+
+        let mut vm = load(source);
+        vm.set_max_string_length(5);
+        let io = BufferIo::new(b"");
+        let output = io.output_handle();
+        vm.set_io(Box::new(io));
+
+        vm.run().unwrap();
+        assert_eq!(output.borrow().as_slice(), b"HELLO");
+    }
+
+    #[test]
+    fn nested_call_without_saving_r7_warns() {
+        let mut vm = load(
+            ".ORIG x3000
+                JSR A
+                HALT
+            A   JSR B
+                HALT
+            B   RET
+            .END",
+        );
+        vm.set_stack_checks(true);
+        let warnings = collect_warnings(&mut vm);
+
+        vm.run().unwrap();
+
+        assert_eq!(
+            *warnings.borrow(),
+            vec![Warning::LostReturnAddress { pc: 0x3002, call: 0x3000 }]
+        );
+    }
+
+    #[test]
+    fn memory_footprint_spans_scattered_accesses() {
+        let mut vm = load(
+            ".ORIG x3000
+                LD R1, SRC
+                LD R2, DST
+                LDR R0, R1, #0
+                STR R0, R2, #5
+                HALT
+            SRC .FILL x4000
+            DST .FILL x2000
+            .END",
+        );
+        assert_eq!(vm.memory_footprint(), (0, 0, 0));
+        vm.enable_access_tracking(true);
+
+        vm.run().unwrap();
+
+        // Five fetches, the two pointer cells, x4000 and x2005
+        assert_eq!(vm.memory_footprint(), (0x2005, 0x4000, 9));
+    }
+
+    #[test]
+    fn strict_mode_warns_on_zero_base_negative_offset() {
+        let source = ".ORIG x3000
+                AND R1, R1, #0
+                LDR R0, R1, #-2
+                HALT
+            .END";
+        let mut vm = load(source);
+        let warnings = collect_warnings(&mut vm);
+        vm.run().unwrap();
+        assert!(warnings.borrow().is_empty());
+
+        let mut vm = load(source);
+        vm.set_strict_mode(true);
+        let warnings = collect_warnings(&mut vm);
+        vm.run().unwrap();
+
+        assert_eq!(
+            *warnings.borrow(),
+            vec![Warning::ZeroBaseNegativeOffset { pc: 0x3001, address: 0xFFFE }]
+        );
+    }
+
+    #[test]
+    fn rti_pops_pc_then_psr() {
+        let mut vm = load(".ORIG x3000\nRTI\n.END");
+        vm.set_register(Registers::R6, 0x2FF0).unwrap();
+        vm.write_memory(0x2FF0, 0x3456).unwrap();
+        vm.write_memory(0x2FF1, 0x0301).unwrap();
+
+        vm.step().unwrap();
+
+        assert_eq!(vm.get_pc(), 0x3456);
+        assert_eq!(vm.psr() & PSR_PRIORITY_MASK, 0x0300);
+        assert_eq!(vm.register(Registers::COND), Flags::POS as u16);
+        assert_eq!(vm.register(Registers::R6), 0x2FF2);
+    }
+
+    #[test]
+    fn run_safely_stops_on_a_self_loop() {
+        let mut vm = load(
+            ".ORIG x3000
+                AND R0, R0, #0
+            LOOP BRz LOOP
+            .END",
+        );
+
+        assert_eq!(vm.run_safely(100), SafeRunOutcome::SelfLoop { pc: 0x3001 });
+    }
+
+    #[test]
+    fn run_safely_stops_on_a_zero_run() {
+        let mut vm = load(".ORIG x3000\nADD R0, R0, #1\n.END");
+
+        assert_eq!(
+            vm.run_safely(ZERO_RUN_LIMIT + 10),
+            SafeRunOutcome::ZeroRun { start: 0x3001 }
+        );
+    }
+
+    #[test]
+    fn run_safely_stops_at_the_step_limit() {
+        let mut vm = load(
+            ".ORIG x3000
+            LOOP ADD R0, R0, #1
+                BRnzp LOOP
+            .END",
+        );
+
+        assert_eq!(vm.run_safely(50), SafeRunOutcome::StepLimitReached);
+        assert_eq!(vm.halt_status(), HaltStatus::StepLimitReached);
+    }
+
+    #[test]
+    fn unique_instructions_lists_each_loop_word_once() {
+        let mut vm = load(
+            ".ORIG x3000
+                AND R1, R1, #0
+                ADD R1, R1, #3
+            LOOP ADD R1, R1, #-1
+                BRp LOOP
+                HALT
+            .END",
+        );
+        assert!(vm.unique_instructions().is_empty());
+        vm.enable_unique_instructions(true);
+
+        vm.run().unwrap();
+
+        let unique = vm.unique_instructions();
+        let words: Vec<u16> = unique.keys().copied().collect();
+        // The loop body ran three times but each word appears once
+        assert_eq!(words, vec![0x03FE, 0x1263, 0x127F, 0x5260, 0xF025]);
+        assert_eq!(unique[&0x5260], disassemble(0x5260, 0x3000));
+    }
+
+    #[test]
+    fn check_memory_does_not_consume_a_pending_key() {
+        let mut vm = load(".ORIG x3000\nHALT\n.END");
+        let mut keyboard = Keyboard::new();
+        keyboard.press(b'a');
+        vm.attach_keyboard(keyboard);
+
+        assert_eq!(vm.check_memory(&[(KBDR, 0)]), vec![]);
+        assert_eq!(vm.call_stack(0xFE04), vec![]);
+
+        assert_eq!(vm.read_memory(KBDR), Some(u16::from(b'a')));
+    }
+
+    #[test]
+    fn step_back_over_a_display_store_writes_nothing_to_the_display() {
+        let mut vm = load(
+            ".ORIG x3000
+                LD R0, CHAR
+                STI R0, DDR_ADDR
+                HALT
+            CHAR .FILL x41
+            DDR_ADDR .FILL xFE06
+            .END",
+        );
+        let io = BufferIo::new(b"");
+        let output = io.output_handle();
+        vm.set_io(Box::new(io));
+        vm.attach_display();
+        vm.enable_journal(4);
+
+        vm.run_for(2).unwrap();
+        vm.step_back().unwrap();
+        vm.step().unwrap();
+
+        assert_eq!(vm.get_pc(), 0x3002);
+        assert_eq!(output.borrow().as_slice(), b"AA");
+    }
+
+    #[test]
+    fn fetch_past_the_end_of_memory_stops_the_vm() {
+        let mut vm = LC3VM::with_memory_size(0x100);
+        vm.initialize(0x00FF, &[0x1021]).unwrap();
+
+        vm.step().unwrap();
+        assert_eq!(vm.get_pc(), 0x0100);
+
+        assert_eq!(vm.step(), Err("Failed to fetch instruction".to_string()));
+        assert!(!vm.is_running());
+        assert!(matches!(vm.halt_status(), HaltStatus::Errored(_)));
+        assert_eq!(vm.step(), Ok(ExecutionResult::Halt));
+    }
+
+    #[test]
+    fn return_stack_follows_nested_calls() {
+        let mut vm = load(
+            ".ORIG x3000
+                JSR A
+                HALT
+            A   ST R7, SAVE
+                JSR B
+                LD R7, SAVE
+                RET
+            B   RET
+            SAVE .BLKW 1
+            .END",
+        );
+
+        vm.step().unwrap();
+        assert_eq!(vm.return_stack(), [0x3001]);
+        vm.run_for(2).unwrap();
+        assert_eq!(vm.return_stack(), [0x3001, 0x3004]);
+        assert!(vm.debug_info().contains("Call depth: 2\n"));
+
+        vm.step().unwrap();
+        assert_eq!(vm.return_stack(), [0x3001]);
+        vm.run_for(2).unwrap();
+        assert!(vm.return_stack().is_empty());
+        assert!(vm.debug_info().contains("Call depth: 0\n"));
+
+        vm.run().unwrap();
+        assert_eq!(vm.get_instruction_count(), 7);
+    }
+
+    #[test]
+    fn debug_info_shows_flags_and_the_next_instruction() {
+        let mut vm = load(
+            ".ORIG x3000
+                ADD R0, R0, #-1
+                HALT
+            .END",
+        );
+        assert!(vm.debug_info().ends_with("Flags: .z.\nNext: ADD R0, R0, #-1"));
+
+        vm.step().unwrap();
+        assert!(vm.debug_info().ends_with("Flags: n..\nNext: HALT"));
+
+        let mut vm = LC3VM::with_memory_size(0x100);
+        vm.set_register(Registers::PC, 0x0100).unwrap();
+        assert!(vm.debug_info().ends_with("Next: <out of range>"));
+    }
+
+    /// Trace sink whose contents stay readable after it is handed to the VM
+    struct SharedBuffer(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.borrow_mut().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn trace_lines_are_written_and_flushed_on_halt() {
+        let mut vm = load(
+            ".ORIG x3000
+                ADD R0, R0, #1
+                HALT
+            .END",
+        );
+        let buffer = Rc::new(RefCell::new(Vec::new()));
+        // Buffered, so nothing reaches `buffer` until the VM flushes
+        vm.enable_trace(Box::new(std::io::BufWriter::new(SharedBuffer(Rc::clone(&buffer)))));
+
+        vm.step().unwrap();
+        assert!(buffer.borrow().is_empty());
+        vm.run().unwrap();
+
+        assert_eq!(
+            String::from_utf8(buffer.borrow().clone()).unwrap(),
+            "x3000  x1021  ADD R0, R0, #1  ; R0=x0001 COND=x0001\n\
+             x3001  xF025  HALT\n"
+        );
+    }
+
+    #[test]
+    fn load_object_runs_from_the_image_origin() {
+        let mut vm = LC3VM::new();
+
+        // ADD R0, R0, #1; HALT at x4000
+        vm.load_object(&[0x40, 0x00, 0x10, 0x21, 0xF0, 0x25]).unwrap();
+
+        assert_eq!(vm.get_pc(), 0x4000);
+        assert_eq!(vm.entry_point(), 0x4000);
+        assert_eq!(vm.read_memory(0x4001), Some(0xF025));
+        vm.run().unwrap();
+        assert_eq!(vm.register(Registers::R0), 1);
+    }
+
+    #[test]
+    fn load_object_from_path_reads_the_file() {
+        let path = std::env::temp_dir().join(format!("lc3-vm-test-{}.obj", std::process::id()));
+        std::fs::write(&path, [0x30, 0x00, 0x10, 0x21, 0xF0, 0x25]).unwrap();
+
+        let mut vm = LC3VM::new();
+        let loaded = vm.load_object_from_path(&path);
+        std::fs::remove_file(&path).unwrap();
+
+        loaded.unwrap();
+        assert_eq!(vm.get_pc(), 0x3000);
+        assert_eq!(vm.read_memory(0x3000), Some(0x1021));
+    }
+
+    #[test]
+    fn load_object_from_a_missing_path_is_an_io_error() {
+        let path = std::env::temp_dir().join("lc3-vm-test-does-not-exist.obj");
+
+        let err = LC3VM::new().load_object_from_path(&path).unwrap_err();
+
+        assert!(matches!(err, LC3Error::IoError(std::io::ErrorKind::NotFound, _)));
+    }
+
+    #[test]
+    fn removed_breakpoint_no_longer_stops_the_run() {
+        let mut vm = load(
+            ".ORIG x3000
+                ADD R0, R0, #1
+                ADD R0, R0, #1
+                HALT
+            .END",
+        );
+        vm.add_breakpoint(0x3001);
+
+        assert!(vm.remove_breakpoint(0x3001));
+        assert!(!vm.remove_breakpoint(0x3001));
+        assert!(!vm.remove_breakpoint(0x3002));
+
+        assert_eq!(vm.run_until_breakpoint(), Ok(BreakReason::Halted));
+        assert_eq!(vm.register(Registers::R0), 2);
+    }
+
+    #[test]
+    fn custom_cycle_model_changes_the_cycle_count() {
+        let source = ".ORIG x3000
+                ADD R0, R0, #1
+                LD R1, VALUE
+                HALT
+            VALUE .FILL #4
+            .END";
+        let mut default = load(source);
+        default.run().unwrap();
+
+        let mut vm = load(source);
+        vm.set_cycle_model(CycleModel { base: 2, memory_access: 10 });
+        vm.run().unwrap();
+
+        // ADD has no data access; LD and HALT's vector read have one each
+        assert_eq!(vm.get_cycle_count(), 2 + 12 + 12);
+        assert_ne!(vm.get_cycle_count(), default.get_cycle_count());
+    }
+}
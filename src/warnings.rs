@@ -0,0 +1,48 @@
+/// Advisory diagnostics the VM can raise while a program runs.
+/// Warnings never stop execution; they are passed to the handler set with `LC3VM::on_warning`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Warning {
+    /// A load read from, or a store wrote to, the declared code region
+    CodeRegionAccess { pc: u16, address: u16, store: bool },
+    /// An LDR/STR through R6 with a positive offset, reaching above the stack top
+    StackAboveTop { pc: u16, offset: i16, store: bool },
+    /// An LDR/STR through a base register holding zero with a negative offset,
+    /// which wraps to a high address (usually an uninitialized base register)
+    ZeroBaseNegativeOffset { pc: u16, address: u16 },
+    /// A JSR/JSRR overwrote R7 before the return address from the call at `call` was saved
+    LostReturnAddress { pc: u16, call: u16 },
+}
+
+impl std::fmt::Display for Warning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Warning::CodeRegionAccess { pc, address, store } => write!(
+                f,
+                "0x{:04X}: {} code region at 0x{:04X}",
+                pc,
+                if *store { "store into" } else { "load from" },
+                address
+            ),
+            Warning::StackAboveTop { pc, offset, store } => write!(
+                f,
+                "0x{:04X}: {} R6{:+} is above the stack top",
+                pc,
+                if *store { "store to" } else { "load from" },
+                offset
+            ),
+            Warning::ZeroBaseNegativeOffset { pc, address } => write!(
+                f,
+                "0x{:04X}: zero base register with negative offset wraps to 0x{:04X}",
+                pc, address
+            ),
+            Warning::LostReturnAddress { pc, call } => write!(
+                f,
+                "0x{:04X}: call overwrites R7 before the return address from 0x{:04X} was saved",
+                pc, call
+            ),
+        }
+    }
+}
+
+/// Callback receiving each warning as it is raised
+pub type WarningHandler = Box<dyn FnMut(&Warning)>;